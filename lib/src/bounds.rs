@@ -1,141 +1,151 @@
 use crate::roads::RoadEdge;
 use crate::roads::RoadNetwork;
-use crate::checker::EvacuationSolution;
+use crate::roads::Network;
+use crate::checker::{EvacuationSolution, Objective};
+use crate::compact::CompactNetwork;
 use crate::evac::EvacuationNode;
+use crate::units::Time;
 use std::time::Instant;
 
-//recursive func to find all the child, add their length and return the total
-pub fn next_node(tree: RoadNetwork, next: Option<RoadEdge>, time: u32, _length: u32) -> u32 {
+//follow the childs from the edge, add their length and return the total
+pub fn next_node(tree: RoadNetwork, next: Option<RoadEdge>, time: Time, _length: Time) -> Time {
     //println!("time: {}", time);
     match next {
+        // safe node reached after the edge
+        Some(x) if tree.evac_info.is_safe(x.child) => time + x.length,
         // There is a child
-        Some(x) => next_node(
-            tree.clone(),
-            tree.get_child_edge(x.child),
-            time + x.length,
-            x.length,
-        ),
-        // safe node reached
+        Some(x) => time + x.length + tree.get_child_path(x.child).iter().map(|e| e.length).sum(),
         None => time,
     }
 }
+//length of the shortest escape route of a node, alternative routes included
+fn shortest_route_length(network: &CompactNetwork, node_id: u32) -> Time {
+    (0..network.evac_info().get_routes(node_id).len())
+        .map(|i| network.route_edges(node_id, i).iter().map(|e| e.length).sum())
+        .min()
+        .unwrap_or(Time::ZERO)
+}
+
 //bound inf is the max of node's evacuation time, for each node, if they are conidered as alone
-pub fn bound_inf(tree: RoadNetwork) -> u32 {
-    let mut result_final = 0; //u32::min_value();
-    let mut result = 0;
-    let mut _road: RoadNetwork;
+pub fn bound_inf(tree: RoadNetwork) -> Time {
+    let mut result_final = Time::ZERO;
 
-    for node in tree.clone().evac_info.nodes {
-        result = match tree.clone().get_child_edge(node.id) {
-            Some(road) => next_node(tree.clone(), tree.get_child_edge(node.id), 0, road.length),
-            None => result,
-        };
+    let network = CompactNetwork::new(&tree);
+
+    for node in &tree.evac_info.nodes {
+        let result = shortest_route_length(&network, node.id);
         //println!("res: {}", result);
         if result > result_final {
             result_final = result;
         }
     }
-    return result_final;
+    result_final
 }
 
 //find max evac_rate for each evac node
-pub fn vec_node(tree: RoadNetwork, mut vec:Vec<u32>, node: EvacuationNode ) -> Vec<u32> {
-    //println!("time: {}", time);
-    let mut next_node = node.clone();
-    match tree.clone().get_child_edge(node.id) {
-        // There is a child
-        Some(x) => {
-            vec.push(x.length);
-            for node in tree.clone().evac_info.nodes {
-                if x.child == node.id{
-                    next_node = node;
-                }
-            }
-
-            vec_node(
-                tree.clone(),
-                vec,
-                next_node,
-            )
-        },
-        // safe node reached
-        None => vec,
-    }
+pub fn vec_node(tree: RoadNetwork, mut vec:Vec<Time>, node: EvacuationNode ) -> Vec<Time> {
+    // lengths of the edges down to the safe node
+    vec.extend(tree.get_child_path(node.id).iter().map(|x| x.length));
+    vec
 }
-pub fn find_max_evac_rate(tree: RoadNetwork, node: EvacuationNode) -> (u32,u32) {
-    let mut tot = 0;
-    let mut res = Vec::new();
-    let mut vec =vec_node(tree.clone(), res, node);
-    vec.sort();
-    let mut vec_len =vec.len()-1;
-    while vec_len >=0 {
-        tot = tot+vec[vec_len];
-        vec_len = vec_len -1;
-    }
-    return (vec[0],tot);
+//shortest and total lengths of the route, both null when the node has no route
+pub fn find_max_evac_rate(tree: RoadNetwork, node: EvacuationNode) -> (Time,Time) {
+    let res = Vec::new();
+    let vec =vec_node(tree, res, node);
+    let tot = vec.iter().sum();
+    (vec.iter().copied().min().unwrap_or(Time::ZERO),tot)
 }
 
 //bound inf but returning EvacuationSolution
 pub fn bound_inf_evac_sol(tree: RoadNetwork) -> EvacuationSolution {
 
     let earlier = Instant::now();
-    let mut nb =0;
-    let mut start_evac =0;
-    let mut max_ev_rate =(0,0);
+    let mut start_evac =Time::ZERO;
     let mut evac_solution = EvacuationSolution::new("solution");
     evac_solution.valid = true;
-    evac_solution.goal_value = bound_inf(tree.clone()) as f32;
+    evac_solution.goal_value = bound_inf(tree.clone()).0 as f32;
 
     for node in tree.clone().evac_info.nodes {
-        max_ev_rate =find_max_evac_rate(tree.clone(), node.clone());
-        nb = (node.population / max_ev_rate.1)+1;
-        evac_solution.add_node(node.id, max_ev_rate.0+nb, start_evac);
-        start_evac = start_evac + max_ev_rate.0+nb;
+        let max_ev_rate =find_max_evac_rate(tree.clone(), node.clone());
+        let nb = Time(node.population.0.checked_div(max_ev_rate.1.0).unwrap_or(0))+Time(1);
+        evac_solution.add_node(node.id, (max_ev_rate.0+nb).0, start_evac.0);
+        start_evac += max_ev_rate.0+nb;
     }
     let now = Instant::now();
     evac_solution.compute_time = now.duration_since(earlier).as_secs() as f32;
-    return evac_solution;
+    evac_solution
 }
 
 //bound sup is the total of node's evacuation time, if they are conidered as alone
-pub fn bound_sup(tree: RoadNetwork) -> u32 {
-    let mut result_final = 0; //u32::min_value();
-    let mut result = 0;
-    let mut _road: RoadNetwork;
+pub fn bound_sup(tree: RoadNetwork) -> Time {
+    let mut result_final = Time::ZERO;
 
-    for node in tree.clone().evac_info.nodes {
-        result = match tree.clone().get_child_edge(node.id) {
-            Some(road) => next_node(tree.clone(), tree.get_child_edge(node.id), 0, road.length),
-            None => result,
-        };
+    let network = CompactNetwork::new(&tree);
+
+    for node in &tree.evac_info.nodes {
+        let result = shortest_route_length(&network, node.id);
         //println!("res: {}", result);
-        result_final = result_final + result;
+        result_final += result;
+    }
+    result_final
+}
+
+//lower bound of an objective, each node being considered as alone, leaving at its max rate
+pub fn bound_inf_objective(tree: RoadNetwork, objective: Objective) -> f32 {
+    let mut person_time = 0u64;
+    let mut weighted = 0u64;
+    let mut population = 0u64;
+
+    let network = CompactNetwork::new(&tree);
+
+    for node in &tree.evac_info.nodes {
+        let length = shortest_route_length(&network, node.id).0 as u64;
+        let (pop, rate) = (node.population.0 as u64, node.max_rate.0.max(1) as u64);
+        // the i-th evacuee cannot leave before i / rate
+        let (groups, rest) = (pop / rate, pop % rate);
+        let waiting = rate * groups * groups.saturating_sub(1) / 2 + rest * groups;
+
+        person_time += pop * length + waiting;
+        if pop > 0 {
+            weighted += pop * (length + pop.div_ceil(rate) - 1);
+        }
+        population += pop;
+    }
+
+    match objective {
+        Objective::Makespan => bound_inf(tree).0 as f32,
+        Objective::TotalPersonTime => person_time as f32,
+        Objective::WeightedCompletion if population > 0 => weighted as f32 / population as f32,
+        Objective::WeightedCompletion | Objective::MaxLateness | Objective::TotalLateness => 0.0,
     }
-    return result_final;
 }
 
 #[cfg(test)]
 mod tests {
     use crate::bounds::bound_inf;
     use crate::bounds::bound_sup;
+    use crate::bounds::bound_inf_objective;
+    use crate::checker::Objective;
     use crate::evac::EvacuationInfo;
     use crate::evac::EvacuationNode;
     use crate::roads::RoadEdge;
     use crate::roads::RoadNetwork;
+    use crate::tests::{example_network, example_solution};
+    use crate::units::{DueDate, Population, Rate, Time};
 
     #[test]
     fn test_bound_inf() {
         let mut evac_info = EvacuationInfo::new(5);
         evac_info.add_node(&EvacuationNode {
             id: 4,
-            population: 512,
-            max_rate: 100,
+            population: Population(512),
+            max_rate: Rate(100),
             route: vec![18, 15, 5],
         });
         evac_info.add_node(&EvacuationNode {
             id: 6,
-            population: 54,
-            max_rate: 10,
+            population: Population(54),
+            max_rate: Rate(10),
             route: vec![19, 13, 5],
         });
         let mut road_network = RoadNetwork::new(evac_info.clone());
@@ -145,9 +155,10 @@ mod tests {
             RoadEdge {
                 parent: 4,
                 child: 18,
-                due_date: 26,
-                length: 54,
-                capacity: 72,
+                due_date: DueDate::At(Time(26)),
+                length: Time(54),
+                capacity: Rate(72),
+                capacity_changes: vec![],
             },
         );
 
@@ -156,9 +167,10 @@ mod tests {
             RoadEdge {
                 parent: 18,
                 child: 15,
-                due_date: 51,
-                length: 45,
-                capacity: 100,
+                due_date: DueDate::At(Time(51)),
+                length: Time(45),
+                capacity: Rate(100),
+                capacity_changes: vec![],
             },
         );
         road_network.add_road_edge(
@@ -166,9 +178,10 @@ mod tests {
             RoadEdge {
                 parent: 15,
                 child: 5,
-                due_date: 51,
-                length: 92,
-                capacity: 31,
+                due_date: DueDate::At(Time(51)),
+                length: Time(92),
+                capacity: Rate(31),
+                capacity_changes: vec![],
             },
         );
 
@@ -177,9 +190,10 @@ mod tests {
             RoadEdge {
                 parent: 6,
                 child: 19,
-                due_date: 26,
-                length: 42,
-                capacity: 55,
+                due_date: DueDate::At(Time(26)),
+                length: Time(42),
+                capacity: Rate(55),
+                capacity_changes: vec![],
             },
         );
 
@@ -188,9 +202,10 @@ mod tests {
             RoadEdge {
                 parent: 19,
                 child: 13,
-                due_date: 31,
-                length: 18,
-                capacity: 80,
+                due_date: DueDate::At(Time(31)),
+                length: Time(18),
+                capacity: Rate(80),
+                capacity_changes: vec![],
             },
         );
         road_network.add_road_edge(
@@ -198,14 +213,15 @@ mod tests {
             RoadEdge {
                 parent: 13,
                 child: 5,
-                due_date: 26,
-                length: 29,
-                capacity: 45,
+                due_date: DueDate::At(Time(26)),
+                length: Time(29),
+                capacity: Rate(45),
+                capacity_changes: vec![],
             },
         );
         let inf = bound_inf(road_network);
         println!("Result found for test bound min: {}", inf);
-        assert_eq!(inf, 191);
+        assert_eq!(inf, Time(191));
     }
 
     #[test]
@@ -213,14 +229,14 @@ mod tests {
         let mut evac_info = EvacuationInfo::new(5);
         evac_info.add_node(&EvacuationNode {
             id: 4,
-            population: 512,
-            max_rate: 100,
+            population: Population(512),
+            max_rate: Rate(100),
             route: vec![18, 15, 5],
         });
         evac_info.add_node(&EvacuationNode {
             id: 6,
-            population: 54,
-            max_rate: 10,
+            population: Population(54),
+            max_rate: Rate(10),
             route: vec![19, 13, 5],
         });
         let mut road_network = RoadNetwork::new(evac_info.clone());
@@ -230,9 +246,10 @@ mod tests {
             RoadEdge {
                 parent: 4,
                 child: 18,
-                due_date: 26,
-                length: 54,
-                capacity: 72,
+                due_date: DueDate::At(Time(26)),
+                length: Time(54),
+                capacity: Rate(72),
+                capacity_changes: vec![],
             },
         );
 
@@ -241,9 +258,10 @@ mod tests {
             RoadEdge {
                 parent: 18,
                 child: 15,
-                due_date: 51,
-                length: 45,
-                capacity: 100,
+                due_date: DueDate::At(Time(51)),
+                length: Time(45),
+                capacity: Rate(100),
+                capacity_changes: vec![],
             },
         );
         road_network.add_road_edge(
@@ -251,9 +269,10 @@ mod tests {
             RoadEdge {
                 parent: 15,
                 child: 5,
-                due_date: 51,
-                length: 92,
-                capacity: 31,
+                due_date: DueDate::At(Time(51)),
+                length: Time(92),
+                capacity: Rate(31),
+                capacity_changes: vec![],
             },
         );
 
@@ -262,9 +281,10 @@ mod tests {
             RoadEdge {
                 parent: 6,
                 child: 19,
-                due_date: 26,
-                length: 42,
-                capacity: 55,
+                due_date: DueDate::At(Time(26)),
+                length: Time(42),
+                capacity: Rate(55),
+                capacity_changes: vec![],
             },
         );
 
@@ -273,9 +293,10 @@ mod tests {
             RoadEdge {
                 parent: 19,
                 child: 13,
-                due_date: 31,
-                length: 18,
-                capacity: 80,
+                due_date: DueDate::At(Time(31)),
+                length: Time(18),
+                capacity: Rate(80),
+                capacity_changes: vec![],
             },
         );
         road_network.add_road_edge(
@@ -283,14 +304,90 @@ mod tests {
             RoadEdge {
                 parent: 13,
                 child: 5,
-                due_date: 26,
-                length: 29,
-                capacity: 45,
+                due_date: DueDate::At(Time(26)),
+                length: Time(29),
+                capacity: Rate(45),
+                capacity_changes: vec![],
             },
         );
 
         let sup = bound_sup(road_network);
         println!("Result found for test bound sup: {}", sup);
-        assert_eq!(sup, 280);
+        assert_eq!(sup, Time(280));
+    }
+
+    #[test]
+    fn test_bound_two_way_road() {
+        // Road 11 - 12 is taken in both directions, node 11 and node 12 first
+        // leaving towards each other.
+        let file_str = "c [evacuation info]\n3 13\n11 10 5 2 12 13\n12 10 5 2 11 13\n14 10 5 2 11 13\nc [graph]\n4 4\n11 12 100 5 10\n12 13 100 5 10\n11 13 100 7 10\n14 11 100 3 10\n";
+        let road_network =
+            RoadNetwork::from_file(file_str, EvacuationInfo::from_file(file_str).unwrap()).unwrap();
+
+        // node 14 follows its route 14 -> 11 -> 13, not its child path
+        // through 12
+        assert_eq!(bound_inf(road_network.clone()), Time(12));
+        assert_eq!(bound_sup(road_network), Time(10 + 12 + 10));
+    }
+
+    #[test]
+    fn test_bound_shortest_route() {
+        let mut road_network = example_network();
+        // node 1 may go straight to 13, its child path goes through 11 and 12
+        road_network.insert_edge(RoadEdge {
+            parent: 1,
+            child: 13,
+            due_date: DueDate::At(Time(40)),
+            length: Time(20),
+            capacity: Rate(7),
+            capacity_changes: vec![],
+        });
+        road_network
+            .evac_info
+            .alternative_routes
+            .insert(1, vec![vec![13]]);
+        assert_eq!(road_network.get_child_path(1).len(), 3);
+
+        assert_eq!(bound_inf(road_network.clone()), Time(25));
+        assert_eq!(bound_sup(road_network.clone()), Time(20 + 25 + 18));
+        // node 1 gets there 8 time units sooner
+        assert_eq!(
+            bound_inf_objective(road_network, Objective::TotalPersonTime),
+            (3129 - 48 * 8) as f32
+        );
+    }
+
+    #[test]
+    fn test_bound_inf_objective() {
+        let road_network = example_network();
+        let solution = example_solution();
+
+        assert_eq!(
+            bound_inf_objective(road_network.clone(), Objective::Makespan),
+            28.0
+        );
+        assert_eq!(
+            bound_inf_objective(road_network.clone(), Objective::TotalPersonTime),
+            3129.0
+        );
+        assert_eq!(
+            bound_inf_objective(road_network.clone(), Objective::WeightedCompletion),
+            3576.0 / 111.0
+        );
+        assert_eq!(
+            bound_inf_objective(road_network.clone(), Objective::MaxLateness),
+            0.0
+        );
+
+        for objective in [
+            Objective::Makespan,
+            Objective::TotalPersonTime,
+            Objective::WeightedCompletion,
+        ] {
+            assert!(
+                bound_inf_objective(road_network.clone(), objective)
+                    <= solution.objective(&road_network, objective)
+            );
+        }
     }
 }
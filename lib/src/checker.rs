@@ -1,7 +1,13 @@
-use crate::roads::RoadNetwork;
+use crate::roads::{CapacityChange, Network, RoadEdge};
+use crate::units::{Population, Rate, Time};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::iter::Iterator;
+use std::str::FromStr;
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EvacuationSolution {
     name: String,
     pub nodes: Vec<SolutionNode>,
@@ -13,21 +19,456 @@ pub struct EvacuationSolution {
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SolutionNode {
     id: u32,
-    pub evacuation_rate: u32,
-    pub start_date: u32,
+    pub evacuation_rate: Rate,
+    pub start_date: Time,
+    /// Waiting periods of the evacuees at intermediate nodes of the route.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub holds: Vec<Hold>,
+    /// Piecewise-constant evacuation rate, by increasing dates. When empty,
+    /// the node evacuates at `evacuation_rate` from `start_date`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rate_schedule: Vec<RateSegment>,
+    /// Index of the escape route taken among the candidate routes of the
+    /// node, see [`EvacuationInfo::get_routes`](crate::evac::EvacuationInfo::get_routes).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub route: usize,
+    /// Number of people of the node evacuated by this stream when the node is
+    /// split across several streams (solution nodes with the same id). `None`
+    /// stands for the whole population.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub share: Option<Population>,
 }
 
-#[derive(Clone)]
-struct NodeCheck {
-    start_id: u32,
-    end_id: u32,
-    start_date: u32,
-    end_date: u32,
-    start_rate: u32,
-    end_rate: u32,
-    max_rate: u32,
+/// Evacuation rate applying from `date` until the date of the next segment.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RateSegment {
+    pub date: Time,
+    pub rate: Rate,
+}
+
+/// Evacuees of a node waiting `duration` time units at route node `node`
+/// before taking the next edge.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Hold {
+    pub node: u32,
+    pub duration: Time,
+}
+
+impl SolutionNode {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Sets a piecewise-constant evacuation rate. `start_date` and
+    /// `evacuation_rate` become the first date and the highest rate of the
+    /// schedule.
+    pub fn set_rate_schedule(&mut self, rate_schedule: Vec<RateSegment>) {
+        if let Some(first) = rate_schedule.first() {
+            self.start_date = first.date;
+        }
+        self.evacuation_rate = rate_schedule
+            .iter()
+            .map(|s| s.rate)
+            .max()
+            .unwrap_or(Rate::ZERO);
+        self.rate_schedule = rate_schedule;
+    }
+
+    /// Returns the rate schedule of the node, a constant rate being a single
+    /// segment.
+    pub fn segments(&self) -> Vec<RateSegment> {
+        if self.rate_schedule.is_empty() {
+            vec![RateSegment {
+                date: self.start_date,
+                rate: self.evacuation_rate,
+            }]
+        } else {
+            self.rate_schedule.clone()
+        }
+    }
+
+    /// Returns the dates at which the evacuees leave the node, with the number
+    /// of people leaving at each of them.
+    ///
+    /// If the rate schedule ends with a null rate, the returned departures may
+    /// not cover the whole population.
+    pub fn departures(&self, population: Population) -> Vec<(Time, Population)> {
+        let mut result = vec![];
+        let segments = self.segments();
+        let mut left = population;
+
+        for (i, segment) in segments.iter().enumerate() {
+            let end = segments.get(i + 1).map(|next| next.date);
+            let mut date = segment.date;
+
+            while left > Population::ZERO
+                && segment.rate > Rate::ZERO
+                && end.is_none_or(|end| date < end)
+            {
+                let count = left.min(segment.rate.during(Time(1)));
+                result.push((date, count));
+                left -= count;
+                date += Time(1);
+            }
+        }
+        result
+    }
+
+    /// Reads a node line of a solution file: `<id> <rate> <start date>`,
+    /// optionally followed by holds `<k> <node 1> <duration 1> ... <node k>
+    /// <duration k>`, then by a rate schedule `<s> <date 1> <rate 1> ...
+    /// <date s> <rate s>`, then by the index of the route taken, then by the
+    /// number of people of the node in this stream.
+    pub fn from_words(words: &[&str]) -> Result<SolutionNode, &'static str> {
+        fn parse<T: FromStr>(words: &[&str], i: usize) -> Result<T, &'static str> {
+            words
+                .get(i)
+                .and_then(|word| word.parse().ok())
+                .ok_or("Error while parsing")
+        }
+        let parse_u32 = |i: usize| parse::<u32>(words, i);
+        let mut node = SolutionNode {
+            id: parse_u32(0)?,
+            evacuation_rate: parse(words, 1)?,
+            start_date: parse(words, 2)?,
+            holds: vec![],
+            rate_schedule: vec![],
+            route: 0,
+            share: None,
+        };
+        let mut i = 3;
+
+        if words.len() > i {
+            let hold_count = parse_u32(i)? as usize;
+            for k in 0..hold_count {
+                node.holds.push(Hold {
+                    node: parse_u32(i + 1 + 2 * k)?,
+                    duration: parse(words, i + 2 + 2 * k)?,
+                });
+            }
+            i += 1 + 2 * hold_count;
+        }
+
+        if words.len() > i {
+            let segment_count = parse_u32(i)? as usize;
+            let mut rate_schedule = vec![];
+            for k in 0..segment_count {
+                rate_schedule.push(RateSegment {
+                    date: parse(words, i + 1 + 2 * k)?,
+                    rate: parse(words, i + 2 + 2 * k)?,
+                });
+            }
+            if !rate_schedule.is_empty() {
+                node.set_rate_schedule(rate_schedule);
+            }
+            i += 1 + 2 * segment_count;
+        }
+
+        if words.len() > i {
+            node.route = parse_u32(i)? as usize;
+            i += 1;
+        }
+
+        if words.len() > i {
+            node.share = Some(parse(words, i)?);
+        }
+
+        Ok(node)
+    }
+
+    /// Writes the node line of a solution file, see
+    /// [`SolutionNode::from_words`].
+    pub fn to_line(&self) -> String {
+        let mut result = format!("{} {} {}", self.id, self.evacuation_rate, self.start_date);
+        let mut extensions = vec![];
+
+        let mut holds = self.holds.len().to_string();
+        for hold in &self.holds {
+            holds.push_str(format!(" {} {}", hold.node, hold.duration).as_str());
+        }
+        extensions.push((holds, self.holds.is_empty()));
+
+        let mut rate_schedule = self.rate_schedule.len().to_string();
+        for segment in &self.rate_schedule {
+            rate_schedule.push_str(format!(" {} {}", segment.date, segment.rate).as_str());
+        }
+        extensions.push((rate_schedule, self.rate_schedule.is_empty()));
+
+        extensions.push((self.route.to_string(), self.route == 0));
+
+        if let Some(share) = self.share {
+            extensions.push((share.to_string(), false));
+        }
+
+        // Optional parts are positional: default ones are only written when a
+        // later part is not.
+        while let Some((_, true)) = extensions.last() {
+            extensions.pop();
+        }
+        for (words, _) in extensions {
+            result.push(' ');
+            result.push_str(words.as_str());
+        }
+
+        result
+    }
+
+    /// Number of people evacuated by this stream out of the `population` of
+    /// the node.
+    pub fn stream_population(&self, population: Population) -> Population {
+        self.share.unwrap_or(population)
+    }
+
+    /// Total waiting time at a route node.
+    pub fn hold_at(&self, node: u32) -> Time {
+        self.holds
+            .iter()
+            .filter(|hold| hold.node == node)
+            .map(|hold| hold.duration)
+            .sum()
+    }
+
+    /// Checks that the stream respects the max rate of its node, evacuates
+    /// its whole population, follows a route leading to a safe node, is only
+    /// held on its way and brings its last evacuee to safety before
+    /// `Time::MAX`.
+    pub(crate) fn is_well_formed(&self, roads: &impl Network) -> bool {
+        let evac_node = match roads.evacuation_node(self.id) {
+            Some(evac_node) => evac_node,
+            None => return false,
+        };
+
+        let segments = self.segments();
+        if segments.iter().any(|s| s.rate > evac_node.max_rate)
+            || segments.windows(2).any(|w| w[0].date >= w[1].date)
+        {
+            return false;
+        }
+
+        // The whole stream must leave.
+        let population = self.stream_population(evac_node.population);
+        let departures = self.departures(population);
+        let departed: Population = departures.iter().map(|(_, count)| count).sum();
+        if departed < population {
+            return false;
+        }
+
+        // The route must lead to a safe node
+        let route = roads.route_edges(self.id, self.route);
+        match route.last() {
+            Some(edge) if roads.evac_info().is_safe(edge.child) => {}
+            _ => return false,
+        }
+
+        // The last evacuee must reach safety before the end of time.
+        let delay = self.route_delay(roads);
+        if departures.last().is_some_and(|(date, _)| {
+            date.checked_add(delay)
+                .and_then(|arrival| arrival.checked_add(Time(1)))
+                .is_none()
+        }) {
+            return false;
+        }
+
+        // Evacuees can only be held on their way, not at the safe node.
+        self.holds
+            .iter()
+            .all(|hold| hold.node != self.id && route.iter().any(|e| e.parent == hold.node))
+    }
+
+    /// Time taken by the evacuees of the stream to reach safety once they
+    /// left: length of the route and holds on the way.
+    pub(crate) fn route_delay(&self, roads: &impl Network) -> Time {
+        roads
+            .route_edges(self.id, self.route)
+            .iter()
+            .map(|e| e.length + self.hold_at(e.parent))
+            .sum()
+    }
+}
+
+/// Number of evacuees entering each route edge at each time unit, as implied
+/// by a solution.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct FlowProfile {
+    /// Edges are identified by their `(parent, child)` pair.
+    pub edges: HashMap<(u32, u32), EdgeLoad>,
+    /// Number of evacuees held at each node over time.
+    pub buffers: HashMap<u32, EdgeLoad>,
+    /// Number of evacuees reaching each safe node, and each node with a
+    /// capacity, at each time unit.
+    pub intakes: HashMap<u32, EdgeLoad>,
+    /// Number of evacuees present at each node with an occupancy limit over
+    /// time: everyone arrived so far at a safe node, counted at each arrival
+    /// date, the held evacuees elsewhere.
+    pub occupancies: HashMap<u32, EdgeLoad>,
+    /// Number of evacuees leaving each evacuation node at each time unit,
+    /// all streams together, against its max rate.
+    pub departures: HashMap<u32, EdgeLoad>,
+}
+
+/// Load of an edge, or occupancy of a node buffer, over time.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EdgeLoad {
+    /// Number of people the edge takes per time unit, or the node holds.
+    pub capacity: Population,
+    /// Changes of the capacity over time, see [`RoadEdge::capacity_changes`].
+    pub capacity_changes: Vec<CapacityChange>,
+    /// Number of people entering the edge at each date, the dates nobody
+    /// enters it being left out.
+    pub load: BTreeMap<Time, Population>,
+}
+
+impl EdgeLoad {
+    pub fn new(capacity: Population) -> EdgeLoad {
+        EdgeLoad {
+            capacity,
+            capacity_changes: vec![],
+            load: BTreeMap::new(),
+        }
+    }
+
+    /// Load of a road edge, following its capacity changes.
+    pub fn for_edge(edge: &RoadEdge) -> EdgeLoad {
+        EdgeLoad {
+            capacity: edge.capacity.during(Time(1)),
+            capacity_changes: edge.capacity_changes.clone(),
+            load: BTreeMap::new(),
+        }
+    }
+
+    /// Capacity at time `date`.
+    pub fn capacity_at(&self, date: Time) -> Population {
+        self.capacity_changes
+            .iter()
+            .take_while(|change| change.date <= date)
+            .last()
+            .map_or(self.capacity, |change| change.capacity.during(Time(1)))
+    }
+
+    /// Number of people entering the edge at time `date`.
+    pub fn count_at(&self, date: Time) -> Population {
+        self.load.get(&date).copied().unwrap_or_default()
+    }
+
+    /// Dates at which people enter the edge, with their number, in
+    /// chronological order.
+    pub fn counts(&self) -> impl Iterator<Item = (Time, Population)> + '_ {
+        self.load.iter().map(|(t, count)| (*t, *count))
+    }
+
+    /// First and last dates, the latter excluded, people enter the edge.
+    pub fn span(&self) -> Option<(Time, Time)> {
+        let (first, _) = self.load.first_key_value()?;
+        let (last, _) = self.load.last_key_value()?;
+        Some((*first, *last + Time(1)))
+    }
+
+    /// Add `count` people during `duration` time units from `date`. Nobody
+    /// is counted from `Time::MAX` on, solutions reaching it failing
+    /// [`EvacuationSolution::check`].
+    pub fn add(&mut self, date: Time, count: Population, duration: Time) {
+        if count == Population::ZERO {
+            return;
+        }
+        for t in date.0..(date + duration).0 {
+            *self.load.entry(Time(t)).or_default() += count;
+        }
+    }
+
+    /// Highest number of people entering the edge during one time unit.
+    pub fn peak(&self) -> Population {
+        self.counts()
+            .map(|(_, count)| count)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Highest ratio between the load and the capacity of the edge at the
+    /// same time unit.
+    pub fn utilisation(&self) -> f32 {
+        self.counts()
+            .map(|(t, count)| count.0 as f32 / self.capacity_at(t).0 as f32)
+            .fold(0.0, f32::max)
+    }
+
+    pub fn is_overloaded(&self) -> bool {
+        self.counts().any(|(t, count)| count > self.capacity_at(t))
+    }
+}
+
+/// How the due dates of the edges count in the goal of a solution.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DueDateMode {
+    /// Nobody may enter an edge from its due date on, nor reach safety after
+    /// the deadline of their node.
+    Hard,
+    /// Evacuees may be late, every person-time unit of lateness adding
+    /// `weight` to the objective.
+    Soft { weight: f32 },
+}
+
+/// Quantity a solution is evaluated on, the lower the better.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Objective {
+    /// Date at which the last evacuee reaches safety.
+    Makespan,
+    /// Sum over the evacuees of their arrival dates, i.e. their exposure.
+    TotalPersonTime,
+    /// Average completion date of the evacuation nodes, weighted by their
+    /// population.
+    WeightedCompletion,
+    /// Longest time an evacuee was late, see [`Lateness`].
+    MaxLateness,
+    /// Person-time units evacuees entered edges after their due dates, see
+    /// [`Lateness`].
+    TotalLateness,
+}
+
+impl Objective {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Objective::Makespan => "makespan",
+            Objective::TotalPersonTime => "total_person_time",
+            Objective::WeightedCompletion => "weighted_completion",
+            Objective::MaxLateness => "max_lateness",
+            Objective::TotalLateness => "total_lateness",
+        }
+    }
+}
+
+/// Lateness of a solution: number of person-time units evacuees entered each
+/// edge after its due date, an evacuee entering at `due_date` being one time
+/// unit late, and reached safety after the deadline of their node. Only late
+/// edges and nodes are listed.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Lateness {
+    pub edges: HashMap<(u32, u32), u64>,
+    pub nodes: HashMap<u32, u64>,
+    /// Lateness of the latest evacuee.
+    pub max: u64,
+}
+
+impl Lateness {
+    pub fn total(&self) -> u64 {
+        self.edges.values().sum::<u64>() + self.nodes.values().sum::<u64>()
+    }
+}
+
+/// Time windows of an evacuation node in a solution. Window ends are
+/// excluded.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NodeSchedule {
+    pub id: u32,
+    /// Departure window at the evacuation node.
+    pub departure: (Time, Time),
+    /// Arrival window at the safe node.
+    pub arrival: (Time, Time),
 }
 
 impl EvacuationSolution {
@@ -42,6 +483,9 @@ impl EvacuationSolution {
         }
     }
 
+    /// Read EvacuationSolution from a file.
+    ///
+    /// See [`SolutionNode::from_words`] for the format of the node lines.
     pub fn from_file(filestr: &str) -> Result<EvacuationSolution, &str> {
         let mut result = EvacuationSolution::new("");
         let mut lines = filestr.lines();
@@ -51,12 +495,7 @@ impl EvacuationSolution {
 
         for _ in 0..node_count {
             let words: Vec<&str> = lines.next().unwrap().split(" ").collect();
-            let node = SolutionNode {
-                id: words[0].parse::<u32>().unwrap(),
-                evacuation_rate: words[1].parse::<u32>().unwrap(),
-                start_date: words[2].parse::<u32>().unwrap(),
-            };
-            result.nodes.push(node);
+            result.nodes.push(SolutionNode::from_words(&words)?);
         }
 
         result.valid = lines.next().unwrap().starts_with("valid");
@@ -70,17 +509,13 @@ impl EvacuationSolution {
     pub fn to_file(&self) -> String {
         let mut result = String::with_capacity(1000);
         result.push_str(self.name.as_str());
-        result.push_str("\n");
+        result.push('\n');
         result.push_str(self.nodes.len().to_string().as_str());
-        result.push_str("\n");
+        result.push('\n');
 
         for node in &self.nodes {
-            result.push_str(node.id.to_string().as_str());
-            result.push_str(" ");
-            result.push_str(node.evacuation_rate.to_string().as_str());
-            result.push_str(" ");
-            result.push_str(node.start_date.to_string().as_str());
-            result.push_str("\n");
+            result.push_str(node.to_line().as_str());
+            result.push('\n');
         }
 
         if self.valid {
@@ -90,11 +525,11 @@ impl EvacuationSolution {
         }
 
         result.push_str(self.goal_value.to_string().as_str());
-        result.push_str("\n");
+        result.push('\n');
         result.push_str(self.compute_time.to_string().as_str());
-        result.push_str("\n");
+        result.push('\n');
         result.push_str(self.method.as_str());
-        result.push_str("\n");
+        result.push('\n');
 
         result
     }
@@ -102,90 +537,399 @@ impl EvacuationSolution {
     pub fn add_node(&mut self, id: u32, evacuation_rate: u32, start_date: u32) {
         self.nodes.push(SolutionNode {
             id,
-            evacuation_rate,
-            start_date,
+            evacuation_rate: Rate(evacuation_rate),
+            start_date: Time(start_date),
+            holds: vec![],
+            rate_schedule: vec![],
+            route: 0,
+            share: None,
         });
     }
 
-    pub fn check(&self, roads: &RoadNetwork) -> bool {
-        let mut all_nodes: Vec<NodeCheck> = vec![];
+    /// Adds a stream evacuating `share` people of node `id` along its route
+    /// number `route`.
+    pub fn add_stream(
+        &mut self,
+        id: u32,
+        evacuation_rate: u32,
+        start_date: u32,
+        route: usize,
+        share: u32,
+    ) {
+        self.add_node(id, evacuation_rate, start_date);
+        let stream = self.nodes.last_mut().unwrap();
+        stream.route = route;
+        stream.share = Some(Population(share));
+    }
+
+    /// Computes the departure and arrival windows of every node of the
+    /// solution.
+    pub fn schedules(&self, roads: &impl Network) -> Vec<NodeSchedule> {
+        let mut result = vec![];
 
-        // Initialization
         for sol_node in &self.nodes {
-            let edge = roads.get_child_edge(sol_node.id).unwrap();
-            let evac_node = roads.evac_info.get_evacuation_data(sol_node.id).unwrap();
+            let departures = match roads.evacuation_node(sol_node.id) {
+                Some(evac_node) => {
+                    sol_node.departures(sol_node.stream_population(evac_node.population))
+                }
+                None => continue,
+            };
+            let (first, last) = match (departures.first(), departures.last()) {
+                (Some(first), Some(last)) => (first.0, last.0 + Time(1)),
+                _ => (sol_node.start_date, sol_node.start_date),
+            };
+            let length = sol_node.route_delay(roads);
 
-            if sol_node.evacuation_rate > edge.capacity {
-                return false;
-            }
-            let duration = (evac_node.population - 1) / sol_node.evacuation_rate + 1;
-            let end_rate = evac_node.population % sol_node.evacuation_rate;
-
-            all_nodes.push(NodeCheck {
-                start_id: sol_node.id,
-                start_date: sol_node.start_date,
-                start_rate: sol_node.evacuation_rate,
-                end_id: edge.child,
-                end_date: sol_node.start_date + duration,
-                end_rate,
-                max_rate: edge.capacity,
+            result.push(NodeSchedule {
+                id: sol_node.id,
+                departure: (first, last),
+                arrival: (first + length, last + length),
             });
         }
 
-        let mut current_nodes: Vec<NodeCheck> = all_nodes.to_vec();
-
-        while !current_nodes.is_empty() {
-            let old_nodes = current_nodes;
-            current_nodes = vec![];
+        result
+    }
 
-            // Create the new nodes
-            for old_node in &old_nodes {
-                if old_node.end_id != roads.evac_info.safe_node {
-                    let old_edge = roads.get_child_edge(old_node.start_id).unwrap();
-                    let edge = roads.get_child_edge(old_node.end_id).unwrap();
+    /// Computes the load of every route edge, and the occupancy of every node
+    /// buffer, over time.
+    pub fn flow_profile(&self, roads: &impl Network) -> FlowProfile {
+        let mut profile = FlowProfile::default();
 
-                    current_nodes.push(NodeCheck {
-                        start_id: edge.parent,
-                        start_date: old_node.start_date + old_edge.length,
-                        start_rate: old_node.start_rate,
-                        end_id: edge.child,
-                        end_date: old_node.end_date + old_edge.length,
-                        end_rate: old_node.end_rate,
-                        max_rate: edge.capacity,
-                    })
+        for sol_node in &self.nodes {
+            let departures = match roads.evacuation_node(sol_node.id) {
+                Some(evac_node) => {
+                    sol_node.departures(sol_node.stream_population(evac_node.population))
                 }
+                None => continue,
+            };
+            let max_rate = roads.evacuation_node(sol_node.id).unwrap().max_rate;
+            let leaving = profile
+                .departures
+                .entry(sol_node.id)
+                .or_insert_with(|| EdgeLoad::new(max_rate.during(Time(1))));
+            for (date, count) in &departures {
+                leaving.add(*date, *count, Time(1));
             }
 
-            // Check if they work with the old list
-            for new_node in &current_nodes {
-                for t in new_node.start_date..(new_node.end_date + 1) {
-                    let mut count = 0;
-
-                    for old_node in &all_nodes {
-                        if old_node.start_id == new_node.start_id {
-                            if old_node.end_date == t {
-                                count += old_node.end_rate;
-                            } else if old_node.start_rate <= t && t < old_node.end_date {
-                                count += old_node.start_rate;
-                            }
-                        }
+            let mut delay = Time::ZERO;
+
+            for edge in roads.route_edges(sol_node.id, sol_node.route).iter() {
+                let hold = sol_node.hold_at(edge.parent);
+
+                if hold > Time::ZERO {
+                    let capacity = roads
+                        .evac_info()
+                        .buffers
+                        .get(&edge.parent)
+                        .copied()
+                        .unwrap_or_default();
+                    let buffer = profile
+                        .buffers
+                        .entry(edge.parent)
+                        .or_insert_with(|| EdgeLoad::new(capacity));
+
+                    for (date, count) in &departures {
+                        buffer.add(*date + delay, *count, hold);
                     }
+                    delay += hold;
+                }
 
-                    if count > new_node.max_rate {
-                        return false;
+                let load = profile
+                    .edges
+                    .entry((edge.parent, edge.child))
+                    .or_insert_with(|| EdgeLoad::for_edge(edge));
+
+                for (date, count) in &departures {
+                    load.add(*date + delay, *count, Time(1));
+                }
+                delay += edge.length;
+
+                if roads.evac_info().is_safe(edge.child)
+                    || roads.evac_info().node_capacities.contains_key(&edge.child)
+                {
+                    let capacity = roads
+                        .evac_info()
+                        .intake_capacity(edge.child)
+                        .unwrap_or(Rate::MAX)
+                        .during(Time(1));
+                    let intake = profile
+                        .intakes
+                        .entry(edge.child)
+                        .or_insert_with(|| EdgeLoad::new(capacity));
+
+                    for (date, count) in &departures {
+                        intake.add(*date + delay, *count, Time(1));
                     }
                 }
-                all_nodes.push(new_node.clone());
             }
         }
 
+        for (id, capacity) in &roads.evac_info().node_capacities {
+            let occupancy = match capacity.occupancy {
+                Some(occupancy) => occupancy,
+                None => continue,
+            };
+            let mut load = EdgeLoad::new(occupancy);
+
+            if roads.evac_info().is_safe(*id) {
+                if let Some(intake) = profile.intakes.get(id) {
+                    let mut total = Population::ZERO;
+                    load.load = intake
+                        .counts()
+                        .map(|(date, count)| {
+                            total += count;
+                            (date, total)
+                        })
+                        .collect();
+                }
+            } else if let Some(buffer) = profile.buffers.get(id) {
+                load.load = buffer.load.clone();
+            }
+            profile.occupancies.insert(*id, load);
+        }
+
+        profile
+    }
+
+    /// Date at which the last evacuee reaches the safe node.
+    pub fn makespan(&self, roads: &impl Network) -> Time {
+        self.schedules(roads)
+            .iter()
+            .filter(|s| s.arrival.1 > s.arrival.0)
+            .map(|s| s.arrival.1 - Time(1))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Lateness of the evacuees on every route edge.
+    pub fn lateness(&self, roads: &impl Network) -> Lateness {
+        let mut result = Lateness::default();
+
+        for ((parent, child), load) in self.flow_profile(roads).edges {
+            let due_date = match roads.edge(parent, child) {
+                Some(edge) => edge.due_date,
+                None => continue,
+            };
+            let mut late = 0;
+            for (t, count) in load.counts() {
+                let delay = due_date.lateness(t);
+                result.max = result.max.max(delay);
+                late += delay * count.0 as u64;
+            }
+
+            if late > 0 {
+                result.edges.insert((parent, child), late);
+            }
+        }
+
+        for sol_node in &self.nodes {
+            let (population, deadline) = match (
+                roads.evacuation_node(sol_node.id),
+                roads.evac_info().deadline(sol_node.id),
+            ) {
+                (Some(evac_node), Some(deadline)) => {
+                    (sol_node.stream_population(evac_node.population), deadline)
+                }
+                _ => continue,
+            };
+            let delay = sol_node.route_delay(roads);
+            let mut late = 0;
+
+            for (date, count) in sol_node.departures(population) {
+                let lateness = (date + delay - deadline).0 as u64;
+                result.max = result.max.max(lateness);
+                late += lateness * count.0 as u64;
+            }
+            if late > 0 {
+                *result.nodes.entry(sol_node.id).or_insert(0) += late;
+            }
+        }
+        result
+    }
+
+    /// Date at which the last evacuee of each evacuation node reaches
+    /// safety, all streams together.
+    pub fn completions(&self, roads: &impl Network) -> HashMap<u32, Time> {
+        let mut result: HashMap<u32, Time> = HashMap::new();
+
+        for schedule in self.schedules(roads) {
+            if schedule.arrival.1 > schedule.arrival.0 {
+                let date = result.entry(schedule.id).or_default();
+                *date = (*date).max(schedule.arrival.1 - Time(1));
+            }
+        }
+        result
+    }
+
+    /// Sum over the evacuees of the date they reach safety.
+    pub fn total_person_time(&self, roads: &impl Network) -> u64 {
+        let mut result = 0;
+
+        for sol_node in &self.nodes {
+            let population = match roads.evacuation_node(sol_node.id) {
+                Some(evac_node) => sol_node.stream_population(evac_node.population),
+                None => continue,
+            };
+            let delay = sol_node.route_delay(roads).0 as u64;
+
+            for (date, count) in sol_node.departures(population) {
+                result += (date.0 as u64 + delay) * count.0 as u64;
+            }
+        }
+        result
+    }
+
+    /// Completion dates of the evacuation nodes averaged with their
+    /// population as weights.
+    pub fn weighted_completion(&self, roads: &impl Network) -> f32 {
+        let mut weighted = 0.0;
+        let mut population = 0;
+        for (id, date) in self.completions(roads) {
+            let weight = roads.evacuation_node(id).unwrap().population.0;
+            weighted += weight as f32 * date.0 as f32;
+            population += weight;
+        }
+        if population == 0 {
+            0.0
+        } else {
+            weighted / population as f32
+        }
+    }
+
+    /// Value of the solution for an objective.
+    pub fn objective(&self, roads: &impl Network, objective: Objective) -> f32 {
+        match objective {
+            Objective::Makespan => self.makespan(roads).0 as f32,
+            Objective::TotalPersonTime => self.total_person_time(roads) as f32,
+            Objective::WeightedCompletion => self.weighted_completion(roads),
+            Objective::MaxLateness => self.lateness(roads).max as f32,
+            Objective::TotalLateness => self.lateness(roads).total() as f32,
+        }
+    }
+
+    /// Goal value of the solution: its objective value, plus the weighted
+    /// lateness when due dates are soft.
+    pub fn goal(&self, roads: &impl Network, objective: Objective, due_dates: DueDateMode) -> f32 {
+        let value = self.objective(roads, objective);
+
+        match due_dates {
+            DueDateMode::Hard => value,
+            DueDateMode::Soft { weight } => value + weight * self.lateness(roads).total() as f32,
+        }
+    }
+
+    /// Checks that every evacuation node with people in it has at least one
+    /// stream, and that the streams of every split node share its whole
+    /// population. Every node of the solution must be an evacuation node.
+    pub(crate) fn check_shares(&self, roads: &impl Network) -> bool {
+        let mut split: HashMap<u32, Vec<Option<Population>>> = HashMap::new();
+        for sol_node in &self.nodes {
+            split.entry(sol_node.id).or_default().push(sol_node.share);
+        }
+        let covered = roads
+            .evac_info()
+            .nodes
+            .iter()
+            .all(|node| node.population == Population::ZERO || split.contains_key(&node.id));
+        if !covered {
+            return false;
+        }
+        for (id, shares) in split {
+            let population = roads.evacuation_node(id).unwrap().population;
+            let total = shares.iter().try_fold(Population::ZERO, |total, share| {
+                share.and_then(|share| total.checked_add(share))
+            });
+
+            if shares.len() > 1 && total != Some(population)
+                || shares.len() == 1 && shares[0].is_some_and(|share| share != population)
+            {
+                return false;
+            }
+        }
         true
     }
+
+    /// Checks the solution like [`check`](Self::check), and that nobody
+    /// enters an edge or reaches safety late when due dates are hard.
+    pub fn check_due_dates(&self, roads: &impl Network, due_dates: DueDateMode) -> bool {
+        self.check(roads) && (due_dates != DueDateMode::Hard || self.lateness(roads).total() == 0)
+    }
+
+    /// Checks that the solution evacuates every node in one stream or in
+    /// streams sharing its whole population, and that it respects the
+    /// evacuation rates of the nodes, the capacities of the edges, the
+    /// buffers of the nodes where evacuees are held and the intake and
+    /// occupancy capacities of the nodes, and that each priority class is
+    /// evacuated before the next ones start. Due dates and deadlines are left
+    /// to [`check_due_dates`](Self::check_due_dates).
+    pub fn check(&self, roads: &impl Network) -> bool {
+        if !self
+            .nodes
+            .iter()
+            .all(|sol_node| sol_node.is_well_formed(roads))
+            || !self.check_shares(roads)
+        {
+            return false;
+        }
+
+        // Priority classes are evacuated one after the other.
+        let completions = self.completions(roads);
+        let mut starts: HashMap<u32, Time> = HashMap::new();
+        for schedule in self.schedules(roads) {
+            if schedule.departure.1 > schedule.departure.0 {
+                let date = starts.entry(schedule.id).or_insert(Time::MAX);
+                *date = (*date).min(schedule.departure.0);
+            }
+        }
+        if !respects_priorities(roads, &completions, &starts) {
+            return false;
+        }
+
+        let profile = self.flow_profile(roads);
+
+        profile.edges.values().all(|load| !load.is_overloaded())
+            && profile
+                .departures
+                .values()
+                .all(|load| !load.is_overloaded())
+            && profile.buffers.values().all(|load| !load.is_overloaded())
+            && profile.intakes.values().all(|load| !load.is_overloaded())
+            && profile
+                .occupancies
+                .values()
+                .all(|load| !load.is_overloaded())
+    }
+}
+
+/// Checks that no node of a priority class starts before every node of the
+/// earlier classes reached safety, from the completion and start dates of
+/// the nodes.
+pub(crate) fn respects_priorities(
+    roads: &impl Network,
+    completions: &HashMap<u32, Time>,
+    starts: &HashMap<u32, Time>,
+) -> bool {
+    completions.iter().all(|(first, completion)| {
+        let class = match roads.evac_info().priority_class(*first) {
+            Some(class) => class,
+            None => return true,
+        };
+        !starts.iter().any(|(next, start)| {
+            roads
+                .evac_info()
+                .priority_class(*next)
+                .is_some_and(|next_class| next_class > class && start <= completion)
+        })
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use super::EvacuationSolution;
+    use super::{DueDateMode, EvacuationSolution, Hold, Lateness, Objective, RateSegment};
+    use crate::evac::{EvacuationInfo, NodeCapacity, NodePriority};
+    use crate::roads::{RoadEdge, RoadNetwork};
+    use crate::tests::{example_network, example_solution, EXAMPLE};
+    use crate::units::{DueDate, Population, Rate, Time};
 
     #[test]
     fn test_parsing_evac_solution() {
@@ -199,4 +943,492 @@ mod tests {
             EvacuationSolution::from_file(evac_solution.to_file().as_str()).unwrap()
         );
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_evac_solution() {
+        let mut evac_solution = EvacuationSolution::new("solution");
+        evac_solution.valid = true;
+        evac_solution.goal_value = 48.0;
+        evac_solution.add_node(5, 10, 4);
+
+        let json = serde_json::to_string(&evac_solution).unwrap();
+        assert!(json.contains("\"method\""));
+        assert_eq!(
+            evac_solution,
+            serde_json::from_str::<EvacuationSolution>(&json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_flow_profile() {
+        let road_network = example_network();
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(1, 5, 0);
+        solution.add_node(2, 3, 0);
+
+        let profile = solution.flow_profile(&road_network);
+        let first = &profile.edges[&(1, 11)];
+        let counts: Vec<u32> = first.counts().map(|(_, count)| count.0).collect();
+        assert_eq!(counts, vec![5, 5, 5, 5, 5, 5, 5, 5, 5, 3]);
+
+        // Node 1 reaches 11 at t=7, node 2 at t=4.
+        let shared = &profile.edges[&(11, 12)];
+        for t in 4..7 {
+            assert_eq!(shared.count_at(Time(t)), Population(3));
+        }
+        assert_eq!(shared.peak(), Population(8));
+        assert_eq!(shared.capacity, Population(10));
+    }
+
+    #[test]
+    fn test_schedules() {
+        let road_network = example_network();
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(1, 5, 0);
+        solution.add_node(3, 3, 2);
+
+        let schedules = solution.schedules(&road_network);
+        assert_eq!(schedules[0].departure, (Time(0), Time(10)));
+        assert_eq!(schedules[0].arrival, (Time(28), Time(38)));
+        assert_eq!(schedules[1].departure, (Time(2), Time(13)));
+        assert_eq!(schedules[1].arrival, (Time(20), Time(31)));
+    }
+
+    #[test]
+    fn test_parsing_holds() {
+        let mut evac_solution = EvacuationSolution::new("solution");
+        evac_solution.add_node(5, 10, 4);
+        evac_solution.nodes[0].holds.push(Hold {
+            node: 7,
+            duration: Time(3),
+        });
+
+        let file = evac_solution.to_file();
+        assert!(file.contains("5 10 4 1 7 3\n"));
+        assert_eq!(
+            evac_solution,
+            EvacuationSolution::from_file(file.as_str()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let road_network = example_network();
+        let mut solution = example_solution();
+        assert!(solution.check(&road_network));
+
+        // 12 -> 13 receives 7 + 3 + 3 people at t=16
+        solution.nodes[0].evacuation_rate = Rate(7);
+        assert!(!solution.check(&road_network));
+
+        solution.nodes[0].evacuation_rate = Rate(5);
+        solution.nodes[2].evacuation_rate = Rate(4);
+        assert!(!solution.check(&road_network));
+    }
+
+    #[test]
+    fn test_check_late_start() {
+        let road_network = example_network();
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(1, 5, 300_000_000);
+        solution.add_node(2, 3, 300_000_000);
+        solution.add_node(3, 3, 300_000_000);
+        assert!(solution.check(&road_network));
+        assert_eq!(solution.makespan(&road_network), Time(300_000_037));
+
+        // The last evacuees would leave after the end of time.
+        solution.nodes[0].start_date = Time(4_294_967_290);
+        assert!(!solution.check(&road_network));
+        // They would reach safety after it.
+        solution.nodes[0].start_date = Time(4_294_967_260);
+        assert!(!solution.check(&road_network));
+    }
+
+    #[test]
+    fn test_check_holds() {
+        let mut road_network = example_network();
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(1, 7, 0);
+        solution.add_node(2, 3, 0);
+        solution.add_node(3, 3, 0);
+        assert!(!solution.check(&road_network));
+
+        // Holding node 1 at 11 lets node 3 leave 12 -> 13 first
+        solution.nodes[0].holds.push(Hold {
+            node: 11,
+            duration: Time(1),
+        });
+        assert!(!solution.check(&road_network));
+
+        road_network.evac_info.buffers.insert(11, Population(7));
+        assert!(solution.check(&road_network));
+        assert_eq!(
+            solution.flow_profile(&road_network).buffers[&11].peak(),
+            Population(7)
+        );
+        assert_eq!(
+            solution.schedules(&road_network)[0].arrival,
+            (Time(29), Time(36))
+        );
+
+        road_network.evac_info.buffers.insert(11, Population(6));
+        assert!(!solution.check(&road_network));
+
+        solution.nodes[0].holds[0].node = 13;
+        assert!(!solution.check(&road_network));
+    }
+
+    #[test]
+    fn test_parsing_rate_schedule() {
+        let solution = EvacuationSolution::from_file(
+            "solution\n2\n1 5 0 0 2 0 5 4 2\n3 3 2 1 12 4 1 2 3\nvalid\n0\n0\nhandmade\n",
+        )
+        .unwrap();
+        assert_eq!(solution.nodes[0].evacuation_rate, Rate(5));
+        assert_eq!(
+            solution.nodes[0].rate_schedule,
+            vec![
+                RateSegment {
+                    date: Time(0),
+                    rate: Rate(5),
+                },
+                RateSegment {
+                    date: Time(4),
+                    rate: Rate(2),
+                }
+            ]
+        );
+        assert_eq!(solution.nodes[1].holds.len(), 1);
+        assert_eq!(solution.nodes[1].rate_schedule.len(), 1);
+        assert_eq!(
+            solution,
+            EvacuationSolution::from_file(solution.to_file().as_str()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rate_schedule() {
+        let road_network = example_network();
+        let mut solution = example_solution();
+        assert_eq!(solution.makespan(&road_network), Time(37));
+
+        // Node 1 meets the last evacuees of node 3 on 12 -> 13 at t=16
+        solution.nodes[0].set_rate_schedule(vec![
+            RateSegment {
+                date: Time(0),
+                rate: Rate(7),
+            },
+            RateSegment {
+                date: Time(2),
+                rate: Rate(5),
+            },
+        ]);
+        assert_eq!(
+            solution.nodes[0].departures(Population(48)),
+            vec![
+                (Time(0), Population(7)),
+                (Time(1), Population(7)),
+                (Time(2), Population(5)),
+                (Time(3), Population(5)),
+                (Time(4), Population(5)),
+                (Time(5), Population(5)),
+                (Time(6), Population(5)),
+                (Time(7), Population(5)),
+                (Time(8), Population(4))
+            ]
+        );
+        assert!(!solution.check(&road_network));
+
+        // Ramping node 1 up to its max rate once node 3 is gone
+        solution.nodes[0].set_rate_schedule(vec![
+            RateSegment {
+                date: Time(0),
+                rate: Rate(1),
+            },
+            RateSegment {
+                date: Time(1),
+                rate: Rate(7),
+            },
+        ]);
+        assert!(solution.check(&road_network));
+        assert_eq!(solution.makespan(&road_network), Time(35));
+
+        // The schedule stops before the whole population is gone
+        solution.nodes[0].set_rate_schedule(vec![
+            RateSegment {
+                date: Time(0),
+                rate: Rate(7),
+            },
+            RateSegment {
+                date: Time(3),
+                rate: Rate(0),
+            },
+        ]);
+        assert!(!solution.check(&road_network));
+    }
+
+    #[test]
+    fn test_check_safe_nodes() {
+        let mut road_network = example_network();
+        let solution = example_solution();
+
+        road_network.evac_info.safe_nodes.insert(13, Some(Rate(11)));
+        assert!(solution.check(&road_network));
+        road_network.evac_info.safe_nodes.insert(13, Some(Rate(10)));
+        assert!(!solution.check(&road_network));
+
+        // Routes of nodes 1 and 2 now stop at 11
+        road_network.evac_info.safe_nodes.insert(11, None);
+        assert!(solution.check(&road_network));
+        assert_eq!(solution.makespan(&road_network), Time(28));
+        assert_eq!(
+            solution.flow_profile(&road_network).intakes[&11].peak(),
+            Population(8)
+        );
+    }
+
+    #[test]
+    fn test_check_node_capacities() {
+        let mut road_network = example_network();
+        let solution = example_solution();
+
+        road_network.evac_info.node_capacities.insert(
+            13,
+            NodeCapacity {
+                intake: None,
+                occupancy: Some(Population(111)),
+            },
+        );
+        assert!(solution.check(&road_network));
+        let profile = solution.flow_profile(&road_network);
+        assert_eq!(profile.occupancies[&13].peak(), Population(111));
+
+        road_network.evac_info.node_capacities.insert(
+            13,
+            NodeCapacity {
+                intake: None,
+                occupancy: Some(Population(110)),
+            },
+        );
+        assert!(!solution.check(&road_network));
+        road_network.evac_info.node_capacities.clear();
+
+        // Nodes 1 and 2 reach 11 together from t=7 to t=14
+        road_network.evac_info.node_capacities.insert(
+            11,
+            NodeCapacity {
+                intake: Some(Rate(8)),
+                occupancy: None,
+            },
+        );
+        assert!(solution.check(&road_network));
+        road_network.evac_info.node_capacities.insert(
+            11,
+            NodeCapacity {
+                intake: Some(Rate(7)),
+                occupancy: None,
+            },
+        );
+        assert!(!solution.check(&road_network));
+    }
+
+    #[test]
+    fn test_check_capacity_changes() {
+        // 12 -> 13 closed from t=10 to t=60
+        let file_str = format!("{}c [capacity changes]\n1\n12 13 2 10 0 60 11\n", EXAMPLE);
+        let road_network =
+            RoadNetwork::from_file(&file_str, EvacuationInfo::from_file(&file_str).unwrap())
+                .unwrap();
+        let mut solution = example_solution();
+
+        assert!(!solution.check(&road_network));
+        let profile = solution.flow_profile(&road_network);
+        assert!(profile.edges[&(12, 13)].utilisation().is_infinite());
+
+        for sol_node in solution.nodes.iter_mut() {
+            sol_node.start_date += Time(60);
+        }
+        assert!(solution.check(&road_network));
+        assert_eq!(solution.makespan(&road_network), Time(97));
+    }
+
+    #[test]
+    fn test_lateness() {
+        let road_network = example_network();
+        let mut solution = example_solution();
+
+        assert_eq!(solution.lateness(&road_network), Lateness::default());
+        assert!(solution.check_due_dates(&road_network, DueDateMode::Hard));
+
+        // Node 3 enters 3 -> 12 (due 28) from t=20 to t=30, 3 people per time
+        // unit, those of t=28, 29 and 30 being late.
+        solution.nodes[2].start_date = Time(20);
+        let lateness = solution.lateness(&road_network);
+        assert_eq!(lateness.edges[&(3, 12)], 3 * (1 + 2 + 3));
+        assert_eq!(lateness.total(), 18);
+        assert_eq!(lateness.max, 3);
+        assert!(solution.check(&road_network));
+        assert!(!solution.check_due_dates(&road_network, DueDateMode::Hard));
+        assert_eq!(
+            solution.goal(
+                &road_network,
+                Objective::Makespan,
+                DueDateMode::Soft { weight: 0.5 }
+            ),
+            solution.makespan(&road_network).0 as f32 + 9.0
+        );
+    }
+
+    #[test]
+    fn test_objectives() {
+        let road_network = example_network();
+        let mut solution = example_solution();
+
+        assert_eq!(solution.objective(&road_network, Objective::Makespan), 37.0);
+        assert_eq!(solution.total_person_time(&road_network), 1551 + 885 + 759);
+        assert_eq!(
+            solution.objective(&road_network, Objective::WeightedCompletion),
+            (48.0 * 37.0 + 30.0 * 34.0 + 33.0 * 28.0) / 111.0
+        );
+        assert_eq!(
+            solution.objective(&road_network, Objective::MaxLateness),
+            0.0
+        );
+
+        // Splitting node 3 does not change its completion
+        solution.nodes.pop();
+        solution.add_stream(3, 3, 0, 0, 18);
+        solution.add_stream(3, 3, 6, 0, 15);
+        assert_eq!(
+            solution.weighted_completion(&road_network),
+            (48.0 * 37.0 + 30.0 * 34.0 + 33.0 * 28.0) / 111.0
+        );
+    }
+
+    #[test]
+    fn test_check_priorities() {
+        let mut road_network = example_network();
+        let mut solution = example_solution();
+
+        // Node 3 is evacuated at t=28, before node 1 may start
+        road_network.evac_info.priorities.insert(
+            3,
+            NodePriority {
+                class: Some(0),
+                deadline: Some(Time(28)),
+            },
+        );
+        road_network.evac_info.priorities.insert(
+            1,
+            NodePriority {
+                class: Some(1),
+                deadline: None,
+            },
+        );
+        assert!(!solution.check(&road_network));
+        solution.nodes[0].start_date = Time(28);
+        assert!(!solution.check(&road_network));
+        solution.nodes[0].start_date = Time(29);
+        assert!(solution.check(&road_network));
+        assert_eq!(solution.lateness(&road_network).nodes.get(&3), None);
+
+        // The last 3 evacuees of node 3 arrive one time unit late
+        road_network
+            .evac_info
+            .priorities
+            .get_mut(&3)
+            .unwrap()
+            .deadline = Some(Time(27));
+        assert_eq!(solution.lateness(&road_network).nodes[&3], 3);
+        // Node 1 enters 1 -> 11 (due 13) until t=38
+        assert_eq!(solution.lateness(&road_network).max, 26);
+    }
+
+    #[test]
+    fn test_parsing_route() {
+        let mut evac_solution = EvacuationSolution::new("solution");
+        evac_solution.add_node(5, 10, 4);
+        evac_solution.add_node(6, 10, 4);
+        evac_solution.nodes[0].route = 2;
+
+        let file = evac_solution.to_file();
+        assert!(file.contains("5 10 4 0 0 2\n6 10 4\n"));
+        assert_eq!(
+            evac_solution,
+            EvacuationSolution::from_file(file.as_str()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parsing_streams() {
+        let mut evac_solution = EvacuationSolution::new("solution");
+        evac_solution.add_stream(5, 10, 4, 0, 30);
+        evac_solution.add_stream(5, 10, 7, 1, 20);
+
+        let file = evac_solution.to_file();
+        assert!(file.contains("5 10 4 0 0 0 30\n5 10 7 0 0 1 20\n"));
+        assert_eq!(
+            evac_solution,
+            EvacuationSolution::from_file(file.as_str()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check_coverage() {
+        let mut road_network = example_network();
+        assert!(!EvacuationSolution::new("empty").check(&road_network));
+
+        // Node 2 is left behind
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(1, 5, 0);
+        solution.add_node(3, 3, 0);
+        assert!(!solution.check(&road_network));
+
+        // Nobody lives in node 2
+        road_network.evac_info.nodes[1].population = Population::ZERO;
+        assert!(solution.check(&road_network));
+    }
+
+    #[test]
+    fn test_check_streams() {
+        let mut road_network = example_network();
+        road_network.evac_info.nodes[2].max_rate = Rate(6);
+        road_network
+            .evac_info
+            .alternative_routes
+            .insert(3, vec![vec![11, 12, 13]]);
+        road_network.insert_edge(RoadEdge {
+            parent: 3,
+            child: 11,
+            due_date: DueDate::At(Time(40)),
+            length: Time(3),
+            capacity: Rate(3),
+            capacity_changes: vec![],
+        });
+
+        // Nodes 1 and 2 are already safe
+        road_network.evac_info.nodes[0].population = Population::ZERO;
+        road_network.evac_info.nodes[1].population = Population::ZERO;
+
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(3, 6, 0);
+        assert!(!solution.check(&road_network));
+
+        // Half of node 3 goes through 11 to relieve 3 -> 12
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_stream(3, 3, 0, 0, 18);
+        solution.add_stream(3, 3, 0, 1, 15);
+        assert!(solution.check(&road_network));
+        assert_eq!(solution.makespan(&road_network), Time(28));
+
+        // Together the streams leave faster than the max rate
+        solution.nodes[1].evacuation_rate = Rate(4);
+        assert!(!solution.check(&road_network));
+
+        // Shares must cover the whole population
+        solution.nodes[1].evacuation_rate = Rate(3);
+        solution.nodes[1].share = Some(Population(14));
+        assert!(!solution.check(&road_network));
+    }
 }
@@ -0,0 +1,262 @@
+use crate::evac::{EvacuationInfo, EvacuationNode};
+use crate::roads::{Network, RoadEdge, RoadNetwork};
+use crate::units::Time;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Immutable compiled form of a [`RoadNetwork`], for evaluating many
+/// solutions on the same instance.
+///
+/// Node ids are mapped to contiguous indices, the edges leaving each node are
+/// stored contiguously (compressed sparse rows) and the edges of every escape
+/// route of every evacuation node are computed once.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CompactNetwork {
+    evac_info: EvacuationInfo,
+    /// Id of the node at each index.
+    ids: Vec<u32>,
+    indices: HashMap<u32, usize>,
+    /// The edges leaving the node at index `i` are
+    /// `edges[offsets[i]..offsets[i + 1]]`.
+    offsets: Vec<usize>,
+    edges: Vec<RoadEdge>,
+    /// Index of the child of each edge.
+    children: Vec<usize>,
+    /// Position in `evac_info.nodes` of the evacuation node at each index.
+    evacuation_nodes: Vec<Option<usize>>,
+    /// Edges of each escape route of the evacuation nodes, in the order of
+    /// `evac_info.nodes`.
+    routes: Vec<Vec<Vec<RoadEdge>>>,
+}
+
+impl CompactNetwork {
+    pub fn new(roads: &RoadNetwork) -> CompactNetwork {
+        let evac_info = roads.evac_info.clone();
+
+        let mut ids: Vec<u32> = roads.nodes.keys().copied().collect();
+        ids.extend(evac_info.nodes.iter().map(|node| node.id));
+        ids.push(evac_info.safe_node);
+        ids.extend(evac_info.safe_nodes.keys());
+        ids.sort_unstable();
+        ids.dedup();
+        let indices: HashMap<u32, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+        // Leaving edges keep the order of the network, the first one being
+        // the child edge of the node.
+        let mut offsets = Vec::with_capacity(ids.len() + 1);
+        let mut edges = vec![];
+        for id in &ids {
+            offsets.push(edges.len());
+            if let Some(keys) = roads.nodes.get(id) {
+                edges.extend(
+                    keys.iter()
+                        .map(|key| &roads.edges[key])
+                        .filter(|edge| edge.parent == *id)
+                        .cloned(),
+                );
+            }
+        }
+        offsets.push(edges.len());
+        let children = edges.iter().map(|edge| indices[&edge.child]).collect();
+
+        let mut evacuation_nodes = vec![None; ids.len()];
+        for (position, node) in evac_info.nodes.iter().enumerate().rev() {
+            evacuation_nodes[indices[&node.id]] = Some(position);
+        }
+        let routes = evac_info
+            .nodes
+            .iter()
+            .map(|node| {
+                (0..evac_info.get_routes(node.id).len())
+                    .map(|i| roads.get_route_edges_for(node.id, i))
+                    .collect()
+            })
+            .collect();
+
+        CompactNetwork {
+            evac_info,
+            ids,
+            indices,
+            offsets,
+            edges,
+            children,
+            evacuation_nodes,
+            routes,
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns the index of the node with id `node_id`.
+    pub fn index(&self, node_id: u32) -> Option<usize> {
+        self.indices.get(&node_id).copied()
+    }
+
+    /// Returns the id of the node at index `index`.
+    pub fn id(&self, index: usize) -> u32 {
+        self.ids[index]
+    }
+
+    /// Returns the edges leaving the node at index `index`.
+    pub fn edges_from(&self, index: usize) -> &[RoadEdge] {
+        &self.edges[self.offsets[index]..self.offsets[index + 1]]
+    }
+
+    /// Returns the indices of the children of the node at index `index`, in
+    /// the order of [`edges_from`](Self::edges_from).
+    pub fn children(&self, index: usize) -> &[usize] {
+        &self.children[self.offsets[index]..self.offsets[index + 1]]
+    }
+
+    /// Returns the first edge leaving a node, see
+    /// [`RoadNetwork::get_child_edge`].
+    pub fn child_edge(&self, node_id: u32) -> Option<&RoadEdge> {
+        self.edges_from(self.index(node_id)?).first()
+    }
+
+    /// Returns the edges met from a node following child edges down to the
+    /// first safe node, see [`RoadNetwork::get_child_path`].
+    pub fn child_path(&self, node_id: u32) -> Vec<&RoadEdge> {
+        let mut result = vec![];
+        let mut current = match self.index(node_id) {
+            Some(index) => index,
+            None => return result,
+        };
+        let mut visited = vec![current];
+
+        while !self.evac_info.is_safe(self.id(current)) {
+            let next = self
+                .children(current)
+                .iter()
+                .position(|child| !visited.contains(child));
+            match next {
+                Some(i) => {
+                    result.push(&self.edges_from(current)[i]);
+                    current = self.children(current)[i];
+                    visited.push(current);
+                }
+                None => break,
+            }
+        }
+        result
+    }
+
+    /// Length of the path following the child edges from a node down to the
+    /// first safe node met.
+    pub fn child_path_length(&self, node_id: u32) -> Time {
+        self.child_path(node_id)
+            .iter()
+            .map(|edge| edge.length)
+            .sum()
+    }
+}
+
+impl Network for CompactNetwork {
+    fn evac_info(&self) -> &EvacuationInfo {
+        &self.evac_info
+    }
+
+    fn evacuation_node(&self, node_id: u32) -> Option<&EvacuationNode> {
+        let position = self.evacuation_nodes[self.index(node_id)?]?;
+        Some(&self.evac_info.nodes[position])
+    }
+
+    fn edge(&self, parent: u32, child: u32) -> Option<&RoadEdge> {
+        self.edges_from(self.index(parent)?)
+            .iter()
+            .find(|edge| edge.child == child)
+    }
+
+    fn route_edges(&self, node_id: u32, route_index: usize) -> Cow<'_, [RoadEdge]> {
+        let position = self
+            .index(node_id)
+            .and_then(|index| self.evacuation_nodes[index]);
+
+        match position {
+            Some(position) => match self.routes[position].get(route_index) {
+                Some(route) => Cow::Borrowed(route),
+                None => Cow::Owned(vec![]),
+            },
+            None => Cow::Owned(self.child_path(node_id).into_iter().cloned().collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::{DueDateMode, Objective};
+    use crate::compact::CompactNetwork;
+    use crate::evac::EvacuationInfo;
+    use crate::roads::{Network, RoadNetwork};
+    use crate::tests::{example_network, example_solution};
+    use crate::units::Time;
+
+    #[test]
+    fn test_compact_network() {
+        let road_network = example_network();
+        let network = CompactNetwork::new(&road_network);
+
+        assert_eq!(network.node_count(), 6);
+        let index = network.index(11).unwrap();
+        assert_eq!(network.id(index), 11);
+        assert_eq!(network.children(index), &[network.index(12).unwrap()]);
+        assert_eq!(network.edges_from(network.index(13).unwrap()), &[]);
+        assert_eq!(network.index(4), None);
+
+        for id in [1, 2, 3, 11] {
+            assert_eq!(network.route_edges(id, 0), road_network.route_edges(id, 0));
+            assert_eq!(
+                network.child_edge(id),
+                road_network.get_child_edge(id).as_ref()
+            );
+        }
+        assert!(network.route_edges(1, 1).is_empty());
+        assert_eq!(network.edge(12, 11), None);
+        assert_eq!(
+            network.evacuation_node(2),
+            road_network.evac_info.get_evacuation_data(2)
+        );
+        assert_eq!(network.evacuation_node(11), None);
+        assert_eq!(network.child_path_length(1), Time(28));
+
+        // Both evaluate a solution the same way.
+        let solution = example_solution();
+        assert!(solution.check(&network));
+        assert_eq!(solution.makespan(&network), Time(37));
+        assert_eq!(
+            solution.flow_profile(&network),
+            solution.flow_profile(&road_network)
+        );
+        assert_eq!(
+            solution.goal(&network, Objective::TotalPersonTime, DueDateMode::Hard),
+            solution.goal(&road_network, Objective::TotalPersonTime, DueDateMode::Hard)
+        );
+    }
+
+    #[test]
+    fn test_two_way_road() {
+        // Road 11 - 12 is taken in both directions, node 11 and node 12 first
+        // leaving towards each other.
+        let file_str = "c [evacuation info]\n3 13\n11 10 5 2 12 13\n12 10 5 2 11 13\n14 10 5 2 11 13\nc [graph]\n4 4\n11 12 100 5 10\n12 13 100 5 10\n11 13 100 7 10\n14 11 100 3 10\n";
+        let road_network =
+            RoadNetwork::from_file(file_str, EvacuationInfo::from_file(file_str).unwrap()).unwrap();
+        let network = CompactNetwork::new(&road_network);
+
+        assert_eq!(network.child_edge(11).unwrap().child, 12);
+        assert_eq!(network.child_edge(12).unwrap().child, 11);
+        assert_eq!(network.child_path_length(11), Time(10));
+        assert_eq!(network.child_path_length(12), Time(12));
+        let path: Vec<u32> = network.child_path(14).iter().map(|e| e.child).collect();
+        assert_eq!(path, vec![11, 12, 13]);
+        assert_eq!(
+            road_network
+                .get_child_path(14)
+                .iter()
+                .map(|e| e.child)
+                .collect::<Vec<u32>>(),
+            path
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::checker::{DueDateMode, EvacuationSolution, Objective};
+use crate::roads::{Contraflow, RoadNetwork};
+use crate::solver::greedy;
+use crate::units::Time;
+
+/// Roads chosen for lane reversal, with the plan built on them.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ContraflowPlan {
+    /// Reversed roads, in the order they were chosen.
+    pub reversed: Vec<Contraflow>,
+    pub cost: u32,
+    /// Network with the lanes reversed, the one `solution` is valid on.
+    pub network: RoadNetwork,
+    pub solution: EvacuationSolution,
+}
+
+/// Valid solutions first, then by increasing makespan.
+fn rank(roads: &RoadNetwork, solution: &EvacuationSolution) -> (bool, Time) {
+    (!solution.valid, solution.makespan(roads))
+}
+
+/// Chooses the roads of [`RoadNetwork::contraflows`] to reverse within
+/// `budget` to minimize the makespan of the greedy solver.
+///
+/// Roads are chosen one at a time: the affordable road giving the lowest
+/// makespan once reversed, the cheapest one on ties, as long as the makespan
+/// drops.
+pub fn plan_contraflow(roads: &RoadNetwork, budget: u32, due_dates: DueDateMode) -> ContraflowPlan {
+    let solve = |network: &RoadNetwork| greedy(network, Objective::Makespan, due_dates);
+    let mut result = ContraflowPlan {
+        reversed: vec![],
+        cost: 0,
+        network: roads.clone(),
+        solution: solve(roads),
+    };
+
+    loop {
+        let mut best: Option<(Contraflow, RoadNetwork, EvacuationSolution)> = None;
+
+        for contraflow in &roads.contraflows {
+            if result.reversed.contains(contraflow) || result.cost + contraflow.cost > budget {
+                continue;
+            }
+            let mut network = result.network.clone();
+            if network
+                .reverse_lanes(contraflow.node1, contraflow.node2)
+                .is_err()
+            {
+                continue;
+            }
+            let solution = solve(&network);
+
+            let better = match &best {
+                Some((chosen, chosen_network, chosen_solution)) => {
+                    (rank(&network, &solution), contraflow.cost)
+                        < (rank(chosen_network, chosen_solution), chosen.cost)
+                }
+                None => true,
+            };
+            if better {
+                best = Some((*contraflow, network, solution));
+            }
+        }
+
+        match best {
+            Some((contraflow, network, solution))
+                if rank(&network, &solution) < rank(&result.network, &result.solution) =>
+            {
+                result.reversed.push(contraflow);
+                result.cost += contraflow.cost;
+                result.network = network;
+                result.solution = solution;
+            }
+            _ => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::DueDateMode;
+    use crate::contraflow::plan_contraflow;
+    use crate::evac::EvacuationInfo;
+    use crate::roads::RoadNetwork;
+    use crate::tests::EXAMPLE;
+
+    #[test]
+    fn test_plan_contraflow() {
+        let file_str = format!("{}c [contraflow]\n2\n1 11 2 0\n12 13 5 0\n", EXAMPLE);
+        let road_network =
+            RoadNetwork::from_file(&file_str, EvacuationInfo::from_file(&file_str).unwrap())
+                .unwrap();
+        let soft = DueDateMode::Soft { weight: 0.0 };
+
+        let plan = plan_contraflow(&road_network, 0, soft);
+        assert!(plan.reversed.is_empty());
+        assert_eq!(plan.network, road_network);
+        let makespan = plan.solution.makespan(&road_network);
+
+        // The road to the safe node is the bottleneck.
+        let plan = plan_contraflow(&road_network, 5, soft);
+        assert_eq!(plan.cost, 5);
+        assert_eq!((plan.reversed[0].node1, plan.reversed[0].node2), (12, 13));
+        assert!(plan.solution.check(&plan.network));
+        assert!(plan.solution.makespan(&plan.network) < makespan);
+    }
+}
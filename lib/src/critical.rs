@@ -0,0 +1,197 @@
+use crate::checker::{EdgeLoad, EvacuationSolution};
+use crate::roads::RoadNetwork;
+use crate::units::{Population, Rate, Time};
+use std::collections::BTreeSet;
+
+/// What keeps the critical node from evacuating faster.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RateLimit {
+    /// The node leaves at its maximum rate.
+    MaxRate,
+    /// The node leaves at the capacity of an edge of its route.
+    Edge(u32, u32),
+    /// The node leaves at the intake capacity of a node of its route.
+    Intake(u32),
+    /// The plan sets a rate below every capacity of the route, usually to
+    /// leave room for other nodes.
+    Plan,
+}
+
+/// Use of an edge of the critical route by the critical node.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CriticalEdge {
+    pub parent: u32,
+    pub child: u32,
+    pub length: Time,
+    /// Time window during which the evacuees of the critical node enter the
+    /// edge, end excluded.
+    pub window: (Time, Time),
+    /// Number of time units of the window during which the edge is used at
+    /// full capacity.
+    pub saturated: u32,
+    /// Other nodes entering the edge during the window, or while the edge is
+    /// used at full capacity before it.
+    pub competitors: Vec<u32>,
+}
+
+/// Explanation of the makespan of a solution: the stream whose last evacuee
+/// arrives last, and what delayed it along its route.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CriticalPath {
+    pub makespan: Time,
+    pub node: u32,
+    /// Index of the critical stream in the solution.
+    pub stream: usize,
+    pub rate: Rate,
+    pub limit: RateLimit,
+    /// Departure window of the critical stream, end excluded.
+    pub departure: (Time, Time),
+    /// Time an evacuee of the critical stream spends on its route, holds
+    /// included.
+    pub route_delay: Time,
+    pub edges: Vec<CriticalEdge>,
+}
+
+/// Finds the critical stream of a solution, the first one among those
+/// arriving last, and explains its arrival date from the flow profile of the
+/// checker. Returns `None` when nobody is evacuated.
+pub fn critical_path(roads: &RoadNetwork, solution: &EvacuationSolution) -> Option<CriticalPath> {
+    // Profiles of each stream alone.
+    let streams: Vec<EvacuationSolution> = solution
+        .nodes
+        .iter()
+        .map(|sol_node| {
+            let mut alone = EvacuationSolution::new("");
+            alone.nodes.push(sol_node.clone());
+            alone
+        })
+        .collect();
+
+    let (stream, schedule) = streams
+        .iter()
+        .enumerate()
+        .filter_map(|(i, alone)| alone.schedules(roads).pop().map(|s| (i, s)))
+        .filter(|(_, s)| s.arrival.1 > s.arrival.0)
+        .max_by_key(|(i, s)| (s.arrival.1, std::cmp::Reverse(*i)))?;
+    let sol_node = &solution.nodes[stream];
+    let evac_node = roads.evac_info.get_evacuation_data(sol_node.id())?;
+    let route = roads.get_route_edges_for(sol_node.id(), sol_node.route);
+
+    let rate = sol_node.evacuation_rate;
+    let limit = if rate >= evac_node.max_rate {
+        RateLimit::MaxRate
+    } else if let Some(edge) = route.iter().find(|edge| edge.capacity <= rate) {
+        RateLimit::Edge(edge.parent, edge.child)
+    } else if let Some(edge) = route.iter().find(|edge| {
+        roads
+            .evac_info
+            .intake_capacity(edge.child)
+            .is_some_and(|intake| intake <= rate)
+    }) {
+        RateLimit::Intake(edge.child)
+    } else {
+        RateLimit::Plan
+    };
+
+    let profile = solution.flow_profile(roads);
+    let own = streams[stream].flow_profile(roads);
+    let others: Vec<(u32, _)> = streams
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != stream)
+        .map(|(i, alone)| (solution.nodes[i].id(), alone.flow_profile(roads)))
+        .collect();
+
+    let mut edges = vec![];
+    for edge in &route {
+        let key = (edge.parent, edge.child);
+        let window = match own.edges.get(&key).and_then(EdgeLoad::span) {
+            Some(window) => window,
+            None => continue,
+        };
+        let load = &profile.edges[&key];
+        let is_saturated = |t: Time| {
+            let count = load.count_at(t);
+            count > Population::ZERO && count >= load.capacity_at(t)
+        };
+
+        let mut competitors = BTreeSet::new();
+        for (id, other) in &others {
+            if *id != sol_node.id()
+                && other.edges.get(&key).is_some_and(|load| {
+                    load.counts()
+                        .map(|(t, _)| t)
+                        .take_while(|t| *t < window.1)
+                        .any(|t| t >= window.0 || is_saturated(t))
+                })
+            {
+                competitors.insert(*id);
+            }
+        }
+
+        edges.push(CriticalEdge {
+            parent: edge.parent,
+            child: edge.child,
+            length: edge.length,
+            window,
+            saturated: load
+                .counts()
+                .filter(|(t, _)| (window.0..window.1).contains(t) && is_saturated(*t))
+                .count() as u32,
+            competitors: competitors.into_iter().collect(),
+        });
+    }
+
+    Some(CriticalPath {
+        makespan: schedule.arrival.1 - Time(1),
+        node: sol_node.id(),
+        stream,
+        rate,
+        limit,
+        departure: schedule.departure,
+        route_delay: schedule.arrival.0 - schedule.departure.0,
+        edges,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::EvacuationSolution;
+    use crate::critical::{critical_path, RateLimit};
+    use crate::tests::{example_network, example_solution};
+    use crate::units::{Rate, Time};
+
+    #[test]
+    fn test_critical_path() {
+        let road_network = example_network();
+        let mut solution = example_solution();
+
+        let path = critical_path(&road_network, &solution).unwrap();
+        assert_eq!(path.makespan, Time(37));
+        assert_eq!(path.node, 1);
+        assert_eq!(path.limit, RateLimit::Plan);
+        assert_eq!(path.departure, (Time(0), Time(10)));
+        assert_eq!(path.route_delay, Time(28));
+
+        let edges: Vec<_> = path.edges.iter().map(|e| (e.parent, e.child)).collect();
+        assert_eq!(edges, vec![(1, 11), (11, 12), (12, 13)]);
+        assert!(path.edges[0].competitors.is_empty());
+        assert_eq!(path.edges[1].window, (Time(7), Time(17)));
+        assert_eq!(path.edges[1].competitors, vec![2]);
+        assert_eq!(path.edges[1].saturated, 0);
+        // Node 3 still enters 12 -> 13 at 16, when node 1 fills it.
+        assert_eq!(path.edges[2].competitors, vec![2, 3]);
+        assert_eq!(path.edges[2].saturated, 1);
+
+        solution.nodes[0].evacuation_rate = Rate(7);
+        let path = critical_path(&road_network, &solution).unwrap();
+        assert_eq!(path.limit, RateLimit::MaxRate);
+        // Node 2 arrives at 34 as well, the first stream is kept.
+        assert_eq!((path.makespan, path.node), (Time(34), 1));
+
+        assert_eq!(
+            critical_path(&road_network, &EvacuationSolution::new("empty")),
+            None
+        );
+    }
+}
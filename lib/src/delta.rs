@@ -0,0 +1,551 @@
+use crate::checker::{
+    respects_priorities, DueDateMode, EdgeLoad, EvacuationSolution, FlowProfile, Objective,
+    SolutionNode,
+};
+use crate::compact::CompactNetwork;
+use crate::roads::Network;
+use crate::units::{Population, Rate, Time};
+use std::collections::{BTreeMap, HashMap};
+
+/// Feasibility and goal value of a solution, see
+/// [`EvacuationSolution::check_due_dates`] and [`EvacuationSolution::goal`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Evaluation {
+    pub valid: bool,
+    pub goal: f32,
+}
+
+/// Evaluates a solution changed one stream at a time, updating only the
+/// loads of the route of the changed stream instead of evaluating the whole
+/// solution again.
+#[derive(Clone, Debug)]
+pub struct DeltaEvaluator<'a> {
+    network: &'a CompactNetwork,
+    solution: EvacuationSolution,
+    objective: Objective,
+    due_dates: DueDateMode,
+    loads: Loads,
+    /// What each stream of the solution adds to the goal.
+    contributions: Vec<Contribution>,
+    /// Streams of each evacuation node.
+    streams: HashMap<u32, Vec<usize>>,
+    /// Number of streams failing [`SolutionNode::is_well_formed`].
+    ill_formed: usize,
+    shares_valid: bool,
+    /// Last arrival dates of the streams, with the number of streams for
+    /// each.
+    arrivals: BTreeMap<Time, usize>,
+    /// Lateness of the latest evacuee of each stream, with the number of
+    /// streams for each.
+    max_lateness: BTreeMap<u64, usize>,
+    person_time: u64,
+    lateness: u64,
+    /// Date at which the last evacuee of each node reaches safety, with the
+    /// population of the node.
+    completions: HashMap<u32, (Time, u64)>,
+    /// Sum over the nodes of their completion date times their population.
+    weighted: u64,
+    weights: u64,
+}
+
+/// Loads of the edges and nodes, as in a [`FlowProfile`], with the number of
+/// time units they are overloaded.
+#[derive(Clone, PartialEq, Debug, Default)]
+struct Loads {
+    /// Occupancies are only kept for the nodes evacuees are held at.
+    profile: FlowProfile,
+    /// Number of people who reached each safe node with an occupancy limit.
+    sheltered: HashMap<u32, Population>,
+    overloads: usize,
+}
+
+/// What a stream adds to the objectives.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+struct Contribution {
+    /// Date the first evacuee leaves and date the last one reaches safety,
+    /// if anybody leaves.
+    window: Option<(Time, Time)>,
+    person_time: u64,
+    lateness: u64,
+    max_lateness: u64,
+}
+
+impl Loads {
+    /// Adds the evacuees of a stream leaving at `departures` to the loads of
+    /// its route, or removes them.
+    fn apply(
+        &mut self,
+        network: &CompactNetwork,
+        sol_node: &SolutionNode,
+        departures: &[(Time, Population)],
+        remove: bool,
+    ) {
+        let evac_info = network.evac_info();
+        let max_rate = match network.evacuation_node(sol_node.id()) {
+            Some(evac_node) => evac_node.max_rate,
+            None => return,
+        };
+        let overloads = &mut self.overloads;
+
+        let leaving = self
+            .profile
+            .departures
+            .entry(sol_node.id())
+            .or_insert_with(|| EdgeLoad::new(max_rate.during(Time(1))));
+        for (date, count) in departures {
+            change_load(leaving, overloads, *date, *count, Time(1), remove);
+        }
+
+        let mut delay = Time::ZERO;
+
+        for edge in network.route_edges(sol_node.id(), sol_node.route).iter() {
+            let hold = sol_node.hold_at(edge.parent);
+
+            if hold > Time::ZERO {
+                let capacity = evac_info
+                    .buffers
+                    .get(&edge.parent)
+                    .copied()
+                    .unwrap_or_default();
+                let buffer = self
+                    .profile
+                    .buffers
+                    .entry(edge.parent)
+                    .or_insert_with(|| EdgeLoad::new(capacity));
+                for (date, count) in departures {
+                    change_load(buffer, overloads, *date + delay, *count, hold, remove);
+                }
+
+                if let (false, Some(occupancy)) = (
+                    evac_info.is_safe(edge.parent),
+                    evac_info.occupancy_capacity(edge.parent),
+                ) {
+                    let held = self
+                        .profile
+                        .occupancies
+                        .entry(edge.parent)
+                        .or_insert_with(|| EdgeLoad::new(occupancy));
+                    for (date, count) in departures {
+                        change_load(held, overloads, *date + delay, *count, hold, remove);
+                    }
+                }
+                delay += hold;
+            }
+
+            let load = self
+                .profile
+                .edges
+                .entry((edge.parent, edge.child))
+                .or_insert_with(|| EdgeLoad::for_edge(edge));
+            for (date, count) in departures {
+                change_load(load, overloads, *date + delay, *count, Time(1), remove);
+            }
+            delay += edge.length;
+
+            if evac_info.is_safe(edge.child) || evac_info.node_capacities.contains_key(&edge.child)
+            {
+                let capacity = evac_info
+                    .intake_capacity(edge.child)
+                    .unwrap_or(Rate::MAX)
+                    .during(Time(1));
+                let intake = self
+                    .profile
+                    .intakes
+                    .entry(edge.child)
+                    .or_insert_with(|| EdgeLoad::new(capacity));
+                for (date, count) in departures {
+                    change_load(intake, overloads, *date + delay, *count, Time(1), remove);
+                }
+            }
+
+            // The occupancy of a safe node only grows, it is overloaded once
+            // too many people arrived.
+            if let (true, Some(occupancy)) = (
+                evac_info.is_safe(edge.child),
+                evac_info.occupancy_capacity(edge.child),
+            ) {
+                let arrived: Population = departures.iter().map(|(_, count)| *count).sum();
+                let sheltered = self.sheltered.entry(edge.child).or_default();
+                let before = *sheltered > occupancy;
+                if remove {
+                    *sheltered -= arrived;
+                } else {
+                    *sheltered += arrived;
+                }
+                count_overload(overloads, before, *sheltered > occupancy);
+            }
+        }
+    }
+}
+
+/// Adds `count` people to a load during `duration` time units from `date`,
+/// or removes them, keeping count of the overloaded time units.
+fn change_load(
+    load: &mut EdgeLoad,
+    overloads: &mut usize,
+    date: Time,
+    count: Population,
+    duration: Time,
+    remove: bool,
+) {
+    for t in (date.0..(date + duration).0).map(Time) {
+        let capacity = load.capacity_at(t);
+        let before = load.count_at(t) > capacity;
+        let after = if remove {
+            load.count_at(t) - count
+        } else {
+            load.count_at(t) + count
+        };
+        if after == Population::ZERO {
+            load.load.remove(&t);
+        } else {
+            load.load.insert(t, after);
+        }
+        count_overload(overloads, before, after > capacity);
+    }
+}
+
+fn count_overload(overloads: &mut usize, before: bool, after: bool) {
+    match (before, after) {
+        (false, true) => *overloads += 1,
+        (true, false) => *overloads -= 1,
+        _ => {}
+    }
+}
+
+fn add_to<K: Ord>(counts: &mut BTreeMap<K, usize>, key: K) {
+    *counts.entry(key).or_insert(0) += 1;
+}
+
+fn remove_from<K: Ord>(counts: &mut BTreeMap<K, usize>, key: K) {
+    if let Some(count) = counts.get_mut(&key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(&key);
+        }
+    }
+}
+
+impl Contribution {
+    /// Computes what a stream leaving at `departures` adds to the
+    /// objectives, in the same way as [`EvacuationSolution::schedules`],
+    /// [`EvacuationSolution::total_person_time`] and
+    /// [`EvacuationSolution::lateness`].
+    fn new(
+        network: &CompactNetwork,
+        sol_node: &SolutionNode,
+        departures: &[(Time, Population)],
+    ) -> Contribution {
+        let mut result = Contribution::default();
+        let route_delay = sol_node.route_delay(network);
+
+        if let (Some(first), Some(last)) = (departures.first(), departures.last()) {
+            result.window = Some((first.0, last.0 + route_delay));
+        }
+        for (date, count) in departures {
+            result.person_time += (*date + route_delay).0 as u64 * count.0 as u64;
+        }
+
+        let mut delay = Time::ZERO;
+        for edge in network.route_edges(sol_node.id(), sol_node.route).iter() {
+            delay += sol_node.hold_at(edge.parent);
+            for (date, count) in departures {
+                let late = edge.due_date.lateness(*date + delay);
+                result.max_lateness = result.max_lateness.max(late);
+                result.lateness += late * count.0 as u64;
+            }
+            delay += edge.length;
+        }
+
+        if let Some(deadline) = network.evac_info().deadline(sol_node.id()) {
+            for (date, count) in departures {
+                let late = (*date + route_delay - deadline).0 as u64;
+                result.max_lateness = result.max_lateness.max(late);
+                result.lateness += late * count.0 as u64;
+            }
+        }
+        result
+    }
+}
+
+impl<'a> DeltaEvaluator<'a> {
+    /// Evaluates `solution` from scratch, keeping what is needed to evaluate
+    /// its changes.
+    pub fn new(
+        network: &'a CompactNetwork,
+        solution: EvacuationSolution,
+        objective: Objective,
+        due_dates: DueDateMode,
+    ) -> DeltaEvaluator<'a> {
+        let mut streams: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (index, sol_node) in solution.nodes.iter().enumerate() {
+            streams.entry(sol_node.id()).or_default().push(index);
+        }
+        let shares_valid = streams
+            .keys()
+            .all(|id| network.evacuation_node(*id).is_some())
+            && solution.check_shares(network);
+
+        let mut result = DeltaEvaluator {
+            network,
+            contributions: vec![Contribution::default(); solution.nodes.len()],
+            solution,
+            objective,
+            due_dates,
+            loads: Loads::default(),
+            streams,
+            ill_formed: 0,
+            shares_valid,
+            arrivals: BTreeMap::new(),
+            max_lateness: BTreeMap::new(),
+            person_time: 0,
+            lateness: 0,
+            completions: HashMap::new(),
+            weighted: 0,
+            weights: 0,
+        };
+        for index in 0..result.solution.nodes.len() {
+            result.add(index);
+        }
+        let ids: Vec<u32> = result.streams.keys().copied().collect();
+        for id in ids {
+            result.update_completion(id);
+        }
+        result
+    }
+
+    pub fn solution(&self) -> &EvacuationSolution {
+        &self.solution
+    }
+
+    pub fn into_solution(self) -> EvacuationSolution {
+        self.solution
+    }
+
+    /// Makes stream `index` of the solution leave at `evacuation_rate` from
+    /// `start_date`, dropping its rate schedule, and evaluates the new
+    /// solution. Only the loads of the route of the stream are updated.
+    pub fn update(&mut self, index: usize, evacuation_rate: Rate, start_date: Time) -> Evaluation {
+        self.remove(index);
+        let sol_node = &mut self.solution.nodes[index];
+        sol_node.evacuation_rate = evacuation_rate;
+        sol_node.start_date = start_date;
+        sol_node.rate_schedule.clear();
+        self.add(index);
+        self.update_completion(self.solution.nodes[index].id());
+
+        self.evaluation()
+    }
+
+    /// Feasibility and goal value of the current solution.
+    pub fn evaluation(&self) -> Evaluation {
+        Evaluation {
+            valid: self.is_valid(),
+            goal: self.goal(),
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.ill_formed == 0
+            && self.shares_valid
+            && self.loads.overloads == 0
+            && (self.due_dates != DueDateMode::Hard || self.lateness == 0)
+            && self.respects_priorities()
+    }
+
+    fn goal(&self) -> f32 {
+        let value = match self.objective {
+            Objective::Makespan => self.arrivals.keys().next_back().map_or(0, |date| date.0) as f32,
+            Objective::TotalPersonTime => self.person_time as f32,
+            Objective::WeightedCompletion if self.weights > 0 => {
+                self.weighted as f32 / self.weights as f32
+            }
+            Objective::WeightedCompletion => 0.0,
+            Objective::MaxLateness => {
+                self.max_lateness.keys().next_back().map_or(0, |late| *late) as f32
+            }
+            Objective::TotalLateness => self.lateness as f32,
+        };
+
+        match self.due_dates {
+            DueDateMode::Hard => value,
+            DueDateMode::Soft { weight } => value + weight * self.lateness as f32,
+        }
+    }
+
+    /// Only looks at the streams of the nodes with a priority class.
+    fn respects_priorities(&self) -> bool {
+        let evac_info = self.network.evac_info();
+        if evac_info.priorities.is_empty() {
+            return true;
+        }
+
+        let mut completions = HashMap::new();
+        let mut starts: HashMap<u32, Time> = HashMap::new();
+        for (id, streams) in &self.streams {
+            if evac_info.priority_class(*id).is_none() {
+                continue;
+            }
+            if let Some((completion, _)) = self.completions.get(id) {
+                completions.insert(*id, *completion);
+            }
+            for (start, _) in streams
+                .iter()
+                .filter_map(|index| self.contributions[*index].window)
+            {
+                let date = starts.entry(*id).or_insert(Time::MAX);
+                *date = (*date).min(start);
+            }
+        }
+        respects_priorities(self.network, &completions, &starts)
+    }
+
+    fn departures(&self, index: usize) -> Vec<(Time, Population)> {
+        let sol_node = &self.solution.nodes[index];
+        match self.network.evacuation_node(sol_node.id()) {
+            Some(evac_node) => {
+                sol_node.departures(sol_node.stream_population(evac_node.population))
+            }
+            None => vec![],
+        }
+    }
+
+    fn add(&mut self, index: usize) {
+        let departures = self.departures(index);
+        let sol_node = &self.solution.nodes[index];
+        let contribution = Contribution::new(self.network, sol_node, &departures);
+
+        self.loads.apply(self.network, sol_node, &departures, false);
+        if !sol_node.is_well_formed(self.network) {
+            self.ill_formed += 1;
+        }
+        if let Some((_, arrival)) = contribution.window {
+            add_to(&mut self.arrivals, arrival);
+        }
+        add_to(&mut self.max_lateness, contribution.max_lateness);
+        self.person_time += contribution.person_time;
+        self.lateness += contribution.lateness;
+        self.contributions[index] = contribution;
+    }
+
+    fn remove(&mut self, index: usize) {
+        let departures = self.departures(index);
+        let sol_node = &self.solution.nodes[index];
+        let contribution = self.contributions[index];
+
+        self.loads.apply(self.network, sol_node, &departures, true);
+        if !sol_node.is_well_formed(self.network) {
+            self.ill_formed -= 1;
+        }
+        if let Some((_, arrival)) = contribution.window {
+            remove_from(&mut self.arrivals, arrival);
+        }
+        remove_from(&mut self.max_lateness, contribution.max_lateness);
+        self.person_time -= contribution.person_time;
+        self.lateness -= contribution.lateness;
+    }
+
+    /// Computes again the completion date of a node from its streams.
+    fn update_completion(&mut self, id: u32) {
+        if let Some((date, weight)) = self.completions.remove(&id) {
+            self.weighted -= date.0 as u64 * weight;
+            self.weights -= weight;
+        }
+
+        let completion = self.streams[&id]
+            .iter()
+            .filter_map(|index| self.contributions[*index].window)
+            .map(|(_, arrival)| arrival)
+            .max();
+        if let (Some(date), Some(evac_node)) = (completion, self.network.evacuation_node(id)) {
+            let weight = evac_node.population.0 as u64;
+            self.weighted += date.0 as u64 * weight;
+            self.weights += weight;
+            self.completions.insert(id, (date, weight));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::{DueDateMode, Objective};
+    use crate::compact::CompactNetwork;
+    use crate::delta::{DeltaEvaluator, Evaluation};
+    use crate::evac::EvacuationInfo;
+    use crate::roads::RoadNetwork;
+    use crate::tests::{example_network, example_solution, EXAMPLE};
+    use crate::units::{Rate, Time};
+
+    #[test]
+    fn test_delta_evaluator() {
+        let solution = example_solution();
+        let moves = [
+            (0, 7, 0),
+            (1, 3, 2),
+            (2, 3, 40),
+            (0, 0, 0),
+            (0, 5, 0),
+            (1, 3, 30),
+            (2, 2, 10),
+            (0, 5, 40),
+        ];
+
+        // Node 1 waits for node 3 in the second instance.
+        let prioritized = format!("{}c [priorities]\n2\n3 0 -\n1 1 -\n", EXAMPLE);
+        for file_str in [EXAMPLE, prioritized.as_str()] {
+            let road_network =
+                RoadNetwork::from_file(file_str, EvacuationInfo::from_file(file_str).unwrap())
+                    .unwrap();
+            let network = CompactNetwork::new(&road_network);
+
+            for objective in [
+                Objective::Makespan,
+                Objective::TotalPersonTime,
+                Objective::WeightedCompletion,
+                Objective::MaxLateness,
+            ] {
+                for due_dates in [DueDateMode::Hard, DueDateMode::Soft { weight: 0.5 }] {
+                    let mut evaluator =
+                        DeltaEvaluator::new(&network, solution.clone(), objective, due_dates);
+                    let mut expected = solution.clone();
+                    assert_eq!(
+                        evaluator.evaluation().valid,
+                        expected.check_due_dates(&road_network, due_dates)
+                    );
+
+                    for (index, rate, start) in moves {
+                        let evaluation = evaluator.update(index, Rate(rate), Time(start));
+                        expected.nodes[index].evacuation_rate = Rate(rate);
+                        expected.nodes[index].start_date = Time(start);
+
+                        assert_eq!(evaluator.solution(), &expected);
+                        assert_eq!(
+                            evaluation.valid,
+                            expected.check_due_dates(&road_network, due_dates)
+                        );
+                        assert_eq!(
+                            evaluation.goal,
+                            expected.goal(&road_network, objective, due_dates)
+                        );
+                    }
+                }
+            }
+        }
+
+        let road_network = example_network();
+        let network = CompactNetwork::new(&road_network);
+        let mut evaluator =
+            DeltaEvaluator::new(&network, solution, Objective::Makespan, DueDateMode::Hard);
+        assert!(evaluator.evaluation().valid);
+        assert!(!evaluator.update(0, Rate(8), Time(0)).valid);
+        assert!(!evaluator.update(0, Rate(0), Time(0)).valid);
+        assert!(!evaluator.update(0, Rate(7), Time(0)).valid);
+        assert_eq!(
+            evaluator.update(0, Rate(5), Time(0)),
+            Evaluation {
+                valid: true,
+                goal: 37.0
+            }
+        );
+    }
+}
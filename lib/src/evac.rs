@@ -1,19 +1,69 @@
+use crate::units::{Population, Rate, Time};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::iter::Iterator;
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EvacuationInfo {
     pub safe_node: u32,
+    /// Safe nodes declared in addition to (or to give an intake capacity to)
+    /// `safe_node`, with their optional intake capacity per time unit.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub safe_nodes: HashMap<u32, Option<Rate>>,
     pub nodes: Vec<EvacuationNode>,
+    /// Escape routes that evacuation nodes may take instead of their own
+    /// `route`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alternative_routes: HashMap<u32, Vec<Vec<u32>>>,
+    /// Limits on the evacuees the nodes can take in.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub node_capacities: HashMap<u32, NodeCapacity>,
+    /// Priority classes and deadlines of evacuation nodes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priorities: HashMap<u32, NodePriority>,
+    /// Range `(min, max)` in which the actual population of an evacuation
+    /// node lies, see [`ScenarioSet::sample`](crate::scenarios::ScenarioSet::sample).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub population_ranges: HashMap<u32, (Population, Population)>,
+    /// Number of people that can be held at once at a route node. Nodes
+    /// without a buffer cannot hold evacuees.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub buffers: HashMap<u32, Population>,
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EvacuationNode {
     pub id: u32,
-    pub population: u32,
-    pub max_rate: u32,
+    pub population: Population,
+    pub max_rate: Rate,
     pub route: Vec<u32>,
 }
 
+/// Limits on the evacuees a node can take in, `None` meaning no limit.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NodeCapacity {
+    /// Number of evacuees reaching the node per time unit.
+    pub intake: Option<Rate>,
+    /// Number of evacuees present at the node at once: everyone who reached
+    /// a safe node, the evacuees held at other nodes.
+    pub occupancy: Option<Population>,
+}
+
+/// Priority of an evacuation node, `None` meaning no constraint.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NodePriority {
+    /// Nodes of a class must be fully evacuated before the nodes of any
+    /// greater class start, class 0 being evacuated first.
+    pub class: Option<u32>,
+    /// Date by which the last evacuee of the node must reach safety.
+    pub deadline: Option<Time>,
+}
+
 enum ParsingState {
     Section,
     Size,
@@ -25,7 +75,13 @@ impl EvacuationInfo {
     pub fn new(safe_node: u32) -> EvacuationInfo {
         EvacuationInfo {
             safe_node,
+            safe_nodes: HashMap::new(),
             nodes: vec![],
+            alternative_routes: HashMap::new(),
+            node_capacities: HashMap::new(),
+            priorities: HashMap::new(),
+            population_ranges: HashMap::new(),
+            buffers: HashMap::new(),
         }
     }
 
@@ -36,10 +92,7 @@ impl EvacuationInfo {
     pub fn from_file(filestr: &str) -> Result<EvacuationInfo, &str> {
         let mut parsing = ParsingState::Section;
         let mut node_count = -1i32;
-        let mut result = EvacuationInfo {
-            safe_node: 0,
-            nodes: vec![],
-        };
+        let mut result = EvacuationInfo::new(0);
 
         for line in filestr.lines() {
             let words: Vec<&str> = line.split(" ").collect();
@@ -62,8 +115,8 @@ impl EvacuationInfo {
                 ParsingState::Node => {
                     let mut node = EvacuationNode {
                         id: words[0].parse::<u32>().unwrap(),
-                        population: words[1].parse::<u32>().unwrap(),
-                        max_rate: words[2].parse::<u32>().unwrap(),
+                        population: words[1].parse().unwrap(),
+                        max_rate: words[2].parse().unwrap(),
                         route: vec![],
                     };
 
@@ -83,12 +136,236 @@ impl EvacuationInfo {
             }
         }
 
+        if let Some(rows) = read_section(filestr, "c [safe nodes]", 1)? {
+            for words in rows {
+                let capacity = match words.get(1) {
+                    Some(word) => Some(parse(word)?),
+                    None => None,
+                };
+                result.safe_nodes.insert(parse(words[0])?, capacity);
+            }
+        }
+
+        if let Some(rows) = read_section(filestr, "c [alternative routes]", 2)? {
+            for words in rows {
+                let count: usize = parse(words[1])?;
+                if words.len() < 2 + count {
+                    return Err("Error while parsing");
+                }
+                let route = words[2..2 + count]
+                    .iter()
+                    .map(|word| parse(word))
+                    .collect::<Result<Vec<u32>, _>>()?;
+
+                result
+                    .alternative_routes
+                    .entry(parse(words[0])?)
+                    .or_default()
+                    .push(route);
+            }
+        }
+
+        if let Some(rows) = read_section(filestr, "c [node capacities]", 3)? {
+            for words in rows {
+                result.node_capacities.insert(
+                    parse(words[0])?,
+                    NodeCapacity {
+                        intake: optional(words[1])?,
+                        occupancy: optional(words[2])?,
+                    },
+                );
+            }
+        }
+
+        if let Some(rows) = read_section(filestr, "c [priorities]", 3)? {
+            for words in rows {
+                result.priorities.insert(
+                    parse(words[0])?,
+                    NodePriority {
+                        class: optional(words[1])?,
+                        deadline: optional(words[2])?,
+                    },
+                );
+            }
+        }
+
+        if let Some(rows) = read_section(filestr, "c [population ranges]", 3)? {
+            for words in rows {
+                let range = (parse(words[1])?, parse(words[2])?);
+                if range.0 > range.1 {
+                    return Err("Error while parsing");
+                }
+                result.population_ranges.insert(parse(words[0])?, range);
+            }
+        }
+
+        if let Some(rows) = read_section(filestr, "c [buffers]", 2)? {
+            for words in rows {
+                result.buffers.insert(parse(words[0])?, parse(words[1])?);
+            }
+        }
+
         match parsing {
             ParsingState::End => Ok(result),
             _ => Err("Error while parsing"),
         }
     }
 
+    /// Write EvacuationInfo in the `c [evacuation info]` section format.
+    pub fn to_file(&self) -> String {
+        let mut result = String::with_capacity(1000);
+        result.push_str("c [evacuation info] format: header with <num evac nodes> <id of safe node> then one line per evac node with <id of the node> <population> <max rate> <k> <v1> ... <vk> where v1,...,vk is the escape route for this node\n");
+        result.push_str(format!("{} {}\n", self.nodes.len(), self.safe_node).as_str());
+
+        for node in &self.nodes {
+            result.push_str(
+                format!(
+                    "{} {} {} {}",
+                    node.id,
+                    node.population,
+                    node.max_rate,
+                    node.route.len()
+                )
+                .as_str(),
+            );
+
+            for v in &node.route {
+                result.push(' ');
+                result.push_str(v.to_string().as_str());
+            }
+            result.push('\n');
+        }
+
+        if !self.safe_nodes.is_empty() {
+            let mut safe_nodes: Vec<_> = self.safe_nodes.iter().collect();
+            safe_nodes.sort();
+
+            result.push_str("c [safe nodes] format: header with <num safe nodes> then one line per safe node <id of the node> [<intake capacity>]\n");
+            result.push_str(format!("{}\n", safe_nodes.len()).as_str());
+            for (id, capacity) in safe_nodes {
+                match capacity {
+                    Some(capacity) => result.push_str(format!("{} {}\n", id, capacity).as_str()),
+                    None => result.push_str(format!("{}\n", id).as_str()),
+                }
+            }
+        }
+
+        if !self.alternative_routes.is_empty() {
+            let mut routes: Vec<_> = self.alternative_routes.iter().collect();
+            routes.sort();
+
+            result.push_str("c [alternative routes] format: header with <num routes> then one line per route <id of the node> <k> <v1> ... <vk>\n");
+            result.push_str(
+                format!("{}\n", routes.iter().map(|(_, r)| r.len()).sum::<usize>()).as_str(),
+            );
+            for (id, node_routes) in routes {
+                for route in node_routes {
+                    result.push_str(format!("{} {}", id, route.len()).as_str());
+                    for v in route {
+                        result.push_str(format!(" {}", v).as_str());
+                    }
+                    result.push('\n');
+                }
+            }
+        }
+
+        if !self.node_capacities.is_empty() {
+            let mut capacities: Vec<_> = self.node_capacities.iter().collect();
+            capacities.sort_by_key(|(id, _)| **id);
+
+            result.push_str("c [node capacities] format: header with <num nodes> then one line per node <id of the node> <max intake rate> <max occupancy>, - meaning no limit\n");
+            result.push_str(format!("{}\n", capacities.len()).as_str());
+            for (id, capacity) in capacities {
+                result.push_str(
+                    format!(
+                        "{} {} {}\n",
+                        id,
+                        write_optional(capacity.intake),
+                        write_optional(capacity.occupancy)
+                    )
+                    .as_str(),
+                );
+            }
+        }
+
+        if !self.priorities.is_empty() {
+            let mut priorities: Vec<_> = self.priorities.iter().collect();
+            priorities.sort_by_key(|(id, _)| **id);
+
+            result.push_str("c [priorities] format: header with <num nodes> then one line per evac node <id of the node> <priority class> <deadline>, - meaning none\n");
+            result.push_str(format!("{}\n", priorities.len()).as_str());
+            for (id, priority) in priorities {
+                result.push_str(
+                    format!(
+                        "{} {} {}\n",
+                        id,
+                        write_optional(priority.class),
+                        write_optional(priority.deadline)
+                    )
+                    .as_str(),
+                );
+            }
+        }
+
+        if !self.population_ranges.is_empty() {
+            let mut ranges: Vec<_> = self.population_ranges.iter().collect();
+            ranges.sort();
+
+            result.push_str("c [population ranges] format: header with <num nodes> then one line per evac node <id of the node> <min population> <max population>\n");
+            result.push_str(format!("{}\n", ranges.len()).as_str());
+            for (id, (min, max)) in ranges {
+                result.push_str(format!("{} {} {}\n", id, min, max).as_str());
+            }
+        }
+
+        if !self.buffers.is_empty() {
+            let mut buffers: Vec<_> = self.buffers.iter().collect();
+            buffers.sort();
+
+            result.push_str("c [buffers] format: header with <num nodes> then one line per node <id of the node> <buffer capacity>\n");
+            result.push_str(format!("{}\n", buffers.len()).as_str());
+            for (id, capacity) in buffers {
+                result.push_str(format!("{} {}\n", id, capacity).as_str());
+            }
+        }
+
+        result
+    }
+
+    /// Returns true if evacuees are safe once they reach the node.
+    pub fn is_safe(&self, node_id: u32) -> bool {
+        node_id == self.safe_node || self.safe_nodes.contains_key(&node_id)
+    }
+
+    /// Returns the number of evacuees a node can take in per time unit,
+    /// `None` meaning no limit.
+    pub fn intake_capacity(&self, node_id: u32) -> Option<Rate> {
+        let safe_node = self.safe_nodes.get(&node_id).copied().flatten();
+        let node = self.node_capacities.get(&node_id).and_then(|c| c.intake);
+
+        match (safe_node, node) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Returns the number of evacuees that can be at a node at once, `None`
+    /// meaning no limit.
+    pub fn occupancy_capacity(&self, node_id: u32) -> Option<Population> {
+        self.node_capacities.get(&node_id).and_then(|c| c.occupancy)
+    }
+
+    /// Returns the priority class of an evacuation node, if any.
+    pub fn priority_class(&self, node_id: u32) -> Option<u32> {
+        self.priorities.get(&node_id).and_then(|p| p.class)
+    }
+
+    /// Returns the date by which an evacuation node must be evacuated, if
+    /// any.
+    pub fn deadline(&self, node_id: u32) -> Option<Time> {
+        self.priorities.get(&node_id).and_then(|p| p.deadline)
+    }
+
     pub fn dump(&self) {
         for n in &self.nodes {
             print!("{}", n.id);
@@ -106,12 +383,47 @@ impl EvacuationInfo {
 
     /// Returns EvacuationNode struct corresponding to the requested node.
     pub fn get_evacuation_data(&self, node_id: u32) -> Option<&EvacuationNode> {
-        for node in &self.nodes {
-            if node.id == node_id {
-                return Some(node);
+        self.nodes.iter().find(|node| node.id == node_id)
+    }
+
+    /// Returns the escape routes a node may take: its own route first, then
+    /// its alternative routes.
+    pub fn get_routes(&self, node_id: u32) -> Vec<&[u32]> {
+        let mut result: Vec<&[u32]> = vec![];
+
+        if let Some(node) = self.get_evacuation_data(node_id) {
+            result.push(&node.route);
+        }
+        if let Some(routes) = self.alternative_routes.get(&node_id) {
+            result.extend(routes.iter().map(|route| route.as_slice()));
+        }
+        result
+    }
+
+    /// Returns the orientations, as `(parent, child)`, in which the escape
+    /// routes (alternatives included) go through the edge between two nodes.
+    pub fn get_edge_orientations(&self, node1: u32, node2: u32) -> Vec<(u32, u32)> {
+        let mut result = vec![];
+        let routes = self.nodes.iter().map(|node| (node.id, &node.route)).chain(
+            self.alternative_routes
+                .iter()
+                .flat_map(|(id, routes)| routes.iter().map(move |route| (*id, route))),
+        );
+
+        for (id, route) in routes {
+            for i in 0..route.len() {
+                let parent_node = if i > 0 { route[i - 1] } else { id };
+
+                if (node1 == route[i] && node2 == parent_node
+                    || node1 == parent_node && node2 == route[i])
+                    && !result.contains(&(parent_node, route[i]))
+                {
+                    result.push((parent_node, route[i]));
+                }
             }
         }
-        None
+
+        result
     }
 
     pub fn get_edge(&self, node1: u32, node2: u32) -> Option<(u32, u32)> {
@@ -130,3 +442,52 @@ impl EvacuationInfo {
         None
     }
 }
+
+pub(crate) fn parse<T: std::str::FromStr>(word: &str) -> Result<T, &'static str> {
+    word.parse::<T>().map_err(|_| "Error while parsing")
+}
+
+/// Reads an optional value, `-` meaning none.
+fn optional<T: std::str::FromStr>(word: &str) -> Result<Option<T>, &'static str> {
+    match word {
+        "-" => Ok(None),
+        _ => parse(word).map(Some),
+    }
+}
+
+/// Writes an optional value, `-` meaning none.
+fn write_optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or(String::from("-"), |v| v.to_string())
+}
+
+/// Reads an optional section of a `.full` file: a line starting with
+/// `header`, then a line with the number of rows, then the rows themselves,
+/// each having at least `columns` words.
+///
+/// Returns the words of every row, or `None` if the section is absent.
+pub(crate) fn read_section<'a>(
+    filestr: &'a str,
+    header: &str,
+    columns: usize,
+) -> Result<Option<Vec<Vec<&'a str>>>, &'static str> {
+    let mut lines = filestr.lines().skip_while(|line| !line.starts_with(header));
+
+    if lines.next().is_none() {
+        return Ok(None);
+    }
+    let count = lines
+        .next()
+        .and_then(|line| line.split(' ').next())
+        .ok_or("Error while parsing")
+        .and_then(parse::<usize>)?;
+
+    let rows: Vec<Vec<&str>> = lines
+        .take(count)
+        .map(|line| line.split(' ').collect())
+        .collect();
+
+    if rows.len() < count || rows.iter().any(|words| words.len() < columns) {
+        return Err("Error while parsing");
+    }
+    Ok(Some(rows))
+}
@@ -1,28 +1,60 @@
 pub mod bounds;
 pub mod checker;
+pub mod compact;
+pub mod contraflow;
+pub mod critical;
+pub mod delta;
 pub mod evac;
+pub mod montecarlo;
+pub mod pareto;
+pub mod plot;
+pub mod portfolio;
 pub mod roads;
-pub mod bounds;
+pub mod replan;
+pub mod routes;
+pub mod scenarios;
+pub mod sensitivity;
+pub mod simulation;
+pub mod solver;
+pub mod units;
 //pub mod localsearch;
 
 #[cfg(test)]
 mod tests {
+    use crate::checker::EvacuationSolution;
     use crate::evac::{EvacuationInfo, EvacuationNode};
     use crate::roads::{RoadEdge, RoadNetwork};
+    use crate::units::{DueDate, Population, Rate, Time};
+
+    /// Example instance shared by the tests of every module.
+    pub(crate) const EXAMPLE: &str = include_str!("../../data/example.full");
+
+    pub(crate) fn example_network() -> RoadNetwork {
+        RoadNetwork::from_file(EXAMPLE, EvacuationInfo::from_file(EXAMPLE).unwrap()).unwrap()
+    }
+
+    /// Plan of the example instance, each node leaving at once.
+    pub(crate) fn example_solution() -> EvacuationSolution {
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(1, 5, 0);
+        solution.add_node(2, 3, 0);
+        solution.add_node(3, 3, 0);
+        solution
+    }
 
     #[test]
     fn test_parsing_evac_info() {
         let mut evac_info = EvacuationInfo::new(5);
         evac_info.add_node(&EvacuationNode {
             id: 4,
-            population: 512,
-            max_rate: 100,
+            population: Population(512),
+            max_rate: Rate(100),
             route: vec![18, 15, 5],
         });
         evac_info.add_node(&EvacuationNode {
             id: 6,
-            population: 54,
-            max_rate: 10,
+            population: Population(54),
+            max_rate: Rate(10),
             route: vec![19, 13, 5],
         });
 
@@ -42,14 +74,14 @@ mod tests {
         let mut evac_info = EvacuationInfo::new(5);
         evac_info.add_node(&EvacuationNode {
             id: 4,
-            population: 512,
-            max_rate: 100,
+            population: Population(512),
+            max_rate: Rate(100),
             route: vec![18, 15, 5],
         });
         evac_info.add_node(&EvacuationNode {
             id: 6,
-            population: 54,
-            max_rate: 10,
+            population: Population(54),
+            max_rate: Rate(10),
             route: vec![19, 13, 5],
         });
         let mut road_network = RoadNetwork::new(evac_info.clone());
@@ -59,9 +91,10 @@ mod tests {
             RoadEdge {
                 parent: 18,
                 child: 15,
-                due_date: 51,
-                length: 45,
-                capacity: 100,
+                due_date: DueDate::At(Time(51)),
+                length: Time(45),
+                capacity: Rate(100),
+                capacity_changes: vec![],
             },
         );
         road_network.add_road_edge(
@@ -69,9 +102,10 @@ mod tests {
             RoadEdge {
                 parent: 15,
                 child: 5,
-                due_date: 51,
-                length: 92,
-                capacity: 31,
+                due_date: DueDate::At(Time(51)),
+                length: Time(92),
+                capacity: Rate(31),
+                capacity_changes: vec![],
             },
         );
 
@@ -80,4 +114,104 @@ mod tests {
         );
         assert_eq!(road_network, info2.unwrap());
     }
+
+    #[test]
+    fn test_writing_road_network() {
+        let file_str = "c [evacuation info] blabla\n2 5\n4 512 100 3 18 15 5\n6 54 10 3 19 13 5\nc [graph] blabla\n19 4\n12 13 51 46 49\n3 5 13 78 38\n18 15 51 45 100\n15 5 51 92 31\n";
+        let evac_info = EvacuationInfo::from_file(file_str).unwrap();
+        let road_network = RoadNetwork::from_file(file_str, evac_info).unwrap();
+
+        let written = road_network.to_file();
+        let info2 = EvacuationInfo::from_file(&written).unwrap();
+        assert_eq!(road_network.evac_info, info2);
+        assert_eq!(
+            road_network,
+            RoadNetwork::from_file(&written, info2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_writing_two_way_road() {
+        // Road 11 - 12 is taken in both directions.
+        let file_str = "c [evacuation info]\n2 13\n11 10 5 2 12 13\n12 10 5 2 11 13\nc [graph]\n3 3\n11 12 100 5 10\n12 13 100 5 10\n11 13 100 5 10\n";
+        let evac_info = EvacuationInfo::from_file(file_str).unwrap();
+        let road_network = RoadNetwork::from_file(file_str, evac_info).unwrap();
+        assert_eq!(road_network.edges.len(), 4);
+        assert!(road_network.get_edge(11, 12).is_some());
+        assert!(road_network.get_edge(12, 11).is_some());
+
+        let written = road_network.to_file();
+        assert!(written.contains("\n3 3\n"));
+        let info2 = EvacuationInfo::from_file(&written).unwrap();
+        assert_eq!(
+            road_network,
+            RoadNetwork::from_file(&written, info2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parsing_buffers() {
+        let file_str = "c [evacuation info] blabla\n1 5\n4 512 100 3 18 15 5\nc [graph] blabla\n19 1\n18 15 51 45 100\nc [buffers] blabla\n2\n18 40\n15 10\n";
+        let evac_info = EvacuationInfo::from_file(file_str).unwrap();
+        assert_eq!(evac_info.buffers.get(&18), Some(&Population(40)));
+        assert_eq!(evac_info.buffers.get(&15), Some(&Population(10)));
+        assert_eq!(evac_info.buffers.get(&4), None);
+        assert_eq!(
+            evac_info,
+            EvacuationInfo::from_file(&evac_info.to_file()).unwrap()
+        );
+
+        assert!(EvacuationInfo::from_file(
+            "c [evacuation info] blabla\n1 5\n4 512 100 3 18 15 5\nc [buffers] blabla\n2\n18 40\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parsing_safe_nodes() {
+        let file_str = "c [evacuation info] blabla\n2 5\n4 512 100 3 18 15 5\n6 54 10 2 19 13\nc [graph] blabla\n19 4\n18 15 51 45 100\n15 5 51 92 31\n6 19 51 5 10\n19 13 51 4 10\nc [safe nodes] blabla\n2\n5 40\n13\n";
+        let evac_info = EvacuationInfo::from_file(file_str).unwrap();
+        assert!(evac_info.is_safe(5));
+        assert!(evac_info.is_safe(13));
+        assert!(!evac_info.is_safe(19));
+        assert_eq!(evac_info.intake_capacity(5), Some(Rate(40)));
+        assert_eq!(evac_info.intake_capacity(13), None);
+        assert_eq!(
+            evac_info,
+            EvacuationInfo::from_file(&evac_info.to_file()).unwrap()
+        );
+
+        let road_network = RoadNetwork::from_file(file_str, evac_info).unwrap();
+        assert_eq!(road_network.get_route_edges(6).len(), 2);
+        assert_eq!(crate::bounds::bound_inf(road_network), Time(9));
+    }
+
+    #[test]
+    fn test_parsing_priorities() {
+        let file_str = "c [evacuation info] blabla\n2 5\n4 512 100 3 18 15 5\n6 54 10 2 19 5\nc [graph] blabla\n19 4\n18 15 51 45 100\n15 5 51 92 31\n6 19 51 5 10\n19 5 51 4 10\nc [priorities] blabla\n2\n6 0 40\n4 - 300\n";
+        let evac_info = EvacuationInfo::from_file(file_str).unwrap();
+        assert_eq!(evac_info.priority_class(6), Some(0));
+        assert_eq!(evac_info.deadline(6), Some(Time(40)));
+        assert_eq!(evac_info.priority_class(4), None);
+        assert_eq!(evac_info.deadline(4), Some(Time(300)));
+        assert_eq!(
+            evac_info,
+            EvacuationInfo::from_file(&evac_info.to_file()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parsing_node_capacities() {
+        let file_str = "c [evacuation info] blabla\n1 5\n6 54 10 2 19 13\nc [graph] blabla\n19 4\n6 19 51 5 10\n19 13 51 4 10\nc [safe nodes] blabla\n1\n5 40\nc [node capacities] blabla\n2\n5 30 500\n19 - 20\n";
+        let evac_info = EvacuationInfo::from_file(file_str).unwrap();
+        assert_eq!(evac_info.intake_capacity(5), Some(Rate(30)));
+        assert_eq!(evac_info.occupancy_capacity(5), Some(Population(500)));
+        assert_eq!(evac_info.intake_capacity(19), None);
+        assert_eq!(evac_info.occupancy_capacity(19), Some(Population(20)));
+        assert_eq!(evac_info.occupancy_capacity(6), None);
+        assert_eq!(
+            evac_info,
+            EvacuationInfo::from_file(&evac_info.to_file()).unwrap()
+        );
+    }
 }
@@ -0,0 +1,355 @@
+use crate::checker::EvacuationSolution;
+use crate::evac::{parse, read_section};
+use crate::roads::RoadNetwork;
+use crate::simulation::simulate;
+use crate::units::{Rate, Time};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Normal, Triangular};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Distribution of the factor a length or a capacity is multiplied by.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Distribution {
+    Fixed,
+    Uniform {
+        min: f32,
+        max: f32,
+    },
+    Triangular {
+        min: f32,
+        mode: f32,
+        max: f32,
+    },
+    /// Normal around 1, negative factors being drawn as 0.
+    Normal {
+        std_dev: f32,
+    },
+}
+
+/// Uncertainty on the roads of an instance, read from the `c [uncertainty]`
+/// section of a `.full` file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Uncertainty {
+    pub lengths: Distribution,
+    pub capacities: Distribution,
+}
+
+/// Outcome of the simulations of a plan on randomly perturbed roads.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MonteCarloReport {
+    pub runs: usize,
+    /// Makespans of the runs the simulation went through, in increasing
+    /// order.
+    pub makespans: Vec<Time>,
+    /// Runs where evacuees had to queue because an edge or a shelter was
+    /// saturated, or where the simulation failed.
+    pub capacity_violations: usize,
+    /// Runs where evacuees entered an edge after its due date or reached
+    /// safety after the deadline of their node.
+    pub due_date_violations: usize,
+}
+
+impl Distribution {
+    /// Draws a factor. Parameters [`Uncertainty::from_file`] would reject,
+    /// such as a minimum above the maximum, draw 1 as `Fixed` does.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f32 {
+        match *self {
+            Distribution::Uniform { min, max } if min <= max && (max - min).is_finite() => {
+                rng.gen_range(min..=max)
+            }
+            Distribution::Triangular { min, mode, max } if min < max => {
+                Triangular::new(min, max, mode).map_or(1.0, |triangular| rng.sample(triangular))
+            }
+            Distribution::Normal { std_dev } if std_dev >= 0.0 => {
+                Normal::new(1.0, std_dev).map_or(1.0, |normal| rng.sample(normal).max(0.0))
+            }
+            _ => 1.0,
+        }
+    }
+
+    fn from_words(words: &[&str]) -> Result<Distribution, &'static str> {
+        let params = words
+            .iter()
+            .skip(1)
+            .map(|word| parse::<f32>(word))
+            .collect::<Result<Vec<f32>, _>>()?;
+        if !params.iter().all(|param| param.is_finite()) {
+            return Err("Error while parsing");
+        }
+
+        let result = match (words[0], params.as_slice()) {
+            ("fixed", []) => Distribution::Fixed,
+            ("uniform", [min, max]) if min <= max => Distribution::Uniform {
+                min: *min,
+                max: *max,
+            },
+            ("triangular", [min, mode, max]) if min <= mode && mode <= max && min < max => {
+                Distribution::Triangular {
+                    min: *min,
+                    mode: *mode,
+                    max: *max,
+                }
+            }
+            ("normal", [std_dev]) if *std_dev >= 0.0 => Distribution::Normal { std_dev: *std_dev },
+            _ => return Err("Error while parsing"),
+        };
+        Ok(result)
+    }
+}
+
+impl Default for Uncertainty {
+    fn default() -> Uncertainty {
+        Uncertainty {
+            lengths: Distribution::Fixed,
+            capacities: Distribution::Fixed,
+        }
+    }
+}
+
+impl Uncertainty {
+    /// Read the `c [uncertainty]` section of a `.full` file, with one row
+    /// `length` or `capacity` followed by the distribution and its
+    /// parameters, for instance `length uniform 0.8 1.2`. Missing rows mean
+    /// no uncertainty.
+    pub fn from_file(filestr: &str) -> Result<Uncertainty, &'static str> {
+        let mut result = Uncertainty::default();
+
+        if let Some(rows) = read_section(filestr, "c [uncertainty]", 2)? {
+            for words in rows {
+                let distribution = Distribution::from_words(&words[1..])?;
+                match words[0] {
+                    "length" => result.lengths = distribution,
+                    "capacity" => result.capacities = distribution,
+                    _ => return Err("Error while parsing"),
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Copy of the network with the length and the capacity of every road
+    /// multiplied by a random factor, the same in both orientations.
+    /// Lengths stay at least 1. Capacities stay at least 1, except during
+    /// closures.
+    pub fn perturb<R: Rng>(&self, roads: &RoadNetwork, rng: &mut R) -> RoadNetwork {
+        let mut result = roads.clone();
+
+        let road = |parent: u32, child: u32| (parent.min(child), parent.max(child));
+        let keys: BTreeSet<(u32, u32)> = result
+            .edges_mut()
+            .map(|edge| road(edge.parent, edge.child))
+            .collect();
+        let factors: BTreeMap<(u32, u32), (f32, f32)> = keys
+            .into_iter()
+            .map(|key| (key, (self.lengths.sample(rng), self.capacities.sample(rng))))
+            .collect();
+
+        let scale = |value: u32, factor: f32| ((value as f32 * factor).round() as u32).max(1);
+        for edge in result.edges_mut() {
+            let (length, capacity) = factors[&road(edge.parent, edge.child)];
+
+            edge.length = Time(scale(edge.length.0, length));
+            if edge.capacity > Rate::ZERO {
+                edge.capacity = Rate(scale(edge.capacity.0, capacity));
+            }
+            for change in edge.capacity_changes.iter_mut() {
+                if change.capacity > Rate::ZERO {
+                    change.capacity = Rate(scale(change.capacity.0, capacity));
+                }
+            }
+        }
+        result
+    }
+
+    /// Simulates a plan on `runs` perturbations of the roads. The same seed
+    /// always gives the same report.
+    pub fn monte_carlo(
+        &self,
+        roads: &RoadNetwork,
+        solution: &EvacuationSolution,
+        runs: usize,
+        seed: u64,
+    ) -> MonteCarloReport {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut result = MonteCarloReport {
+            runs,
+            ..MonteCarloReport::default()
+        };
+
+        for _ in 0..runs {
+            match simulate(&self.perturb(roads, &mut rng), solution) {
+                Ok(report) => {
+                    if !report.queues.is_empty() {
+                        result.capacity_violations += 1;
+                    }
+                    if report.lateness.total() > 0 {
+                        result.due_date_violations += 1;
+                    }
+                    result.makespans.push(report.makespan);
+                }
+                Err(_) => result.capacity_violations += 1,
+            }
+        }
+        result.makespans.sort_unstable();
+        result
+    }
+}
+
+impl MonteCarloReport {
+    pub fn mean_makespan(&self) -> f32 {
+        let total: u64 = self
+            .makespans
+            .iter()
+            .map(|makespan| makespan.0 as u64)
+            .sum();
+        total as f32 / self.makespans.len().max(1) as f32
+    }
+
+    /// Makespan below which a share `q` of the simulated runs end.
+    pub fn quantile(&self, q: f32) -> Option<Time> {
+        let last = self.makespans.len().checked_sub(1)?;
+        let index = (q.clamp(0.0, 1.0) * last as f32).round() as usize;
+        Some(self.makespans[index])
+    }
+
+    pub fn capacity_violation_probability(&self) -> f32 {
+        self.capacity_violations as f32 / self.runs.max(1) as f32
+    }
+
+    pub fn due_date_violation_probability(&self) -> f32 {
+        self.due_date_violations as f32 / self.runs.max(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::montecarlo::{Distribution, Uncertainty};
+    use crate::tests::{example_network, example_solution, EXAMPLE};
+    use crate::units::{Rate, Time};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_parsing_uncertainty() {
+        let file_str = format!(
+            "{}c [uncertainty] blabla\n2\nlength uniform 0.8 1.2\ncapacity triangular 0.5 1 1\n",
+            EXAMPLE
+        );
+        let uncertainty = Uncertainty::from_file(&file_str).unwrap();
+        assert_eq!(
+            uncertainty.lengths,
+            Distribution::Uniform { min: 0.8, max: 1.2 }
+        );
+        assert_eq!(
+            uncertainty.capacities,
+            Distribution::Triangular {
+                min: 0.5,
+                mode: 1.0,
+                max: 1.0
+            }
+        );
+        assert_eq!(
+            Uncertainty::from_file(EXAMPLE).unwrap(),
+            Uncertainty::default()
+        );
+
+        let file_str = format!("{}c [uncertainty]\n1\nlength uniform 1.2 0.8\n", EXAMPLE);
+        assert!(Uncertainty::from_file(&file_str).is_err());
+        let file_str = format!("{}c [uncertainty]\n1\nlength normal inf\n", EXAMPLE);
+        assert!(Uncertainty::from_file(&file_str).is_err());
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for distribution in [
+            Distribution::Uniform { min: 1.2, max: 0.8 },
+            Distribution::Uniform {
+                min: 0.8,
+                max: f32::INFINITY,
+            },
+            Distribution::Triangular {
+                min: 0.5,
+                mode: 2.0,
+                max: 1.5,
+            },
+            Distribution::Normal { std_dev: -1.0 },
+            Distribution::Normal { std_dev: f32::NAN },
+        ] {
+            assert_eq!(distribution.sample(&mut rng), 1.0);
+        }
+
+        let factor = Distribution::Uniform { min: 0.8, max: 1.2 }.sample(&mut rng);
+        assert!((0.8..=1.2).contains(&factor));
+    }
+
+    #[test]
+    fn test_perturb() {
+        let road_network = example_network();
+        let uncertainty = Uncertainty {
+            lengths: Distribution::Uniform { min: 0.5, max: 2.0 },
+            capacities: Distribution::Triangular {
+                min: 0.5,
+                mode: 1.0,
+                max: 1.5,
+            },
+        };
+        let perturbed = uncertainty.perturb(&road_network, &mut StdRng::seed_from_u64(3));
+
+        let scaled = |value: u32, factor: f32| ((value as f32 * factor).round() as u32).max(1);
+        for edge in road_network.edges.values() {
+            let other = perturbed.get_edge(edge.parent, edge.child).unwrap();
+            assert!(
+                (Time(scaled(edge.length.0, 0.5))..=Time(scaled(edge.length.0, 2.0)))
+                    .contains(&other.length)
+            );
+            assert!(
+                (Rate(scaled(edge.capacity.0, 0.5))..=Rate(scaled(edge.capacity.0, 1.5)))
+                    .contains(&other.capacity)
+            );
+        }
+        assert_ne!(perturbed, road_network);
+        assert_eq!(
+            perturbed,
+            uncertainty.perturb(&road_network, &mut StdRng::seed_from_u64(3))
+        );
+        assert_ne!(
+            perturbed,
+            uncertainty.perturb(&road_network, &mut StdRng::seed_from_u64(4))
+        );
+        assert_eq!(
+            Uncertainty::default().perturb(&road_network, &mut StdRng::seed_from_u64(3)),
+            road_network
+        );
+    }
+
+    #[test]
+    fn test_monte_carlo() {
+        let road_network = example_network();
+        let solution = example_solution();
+
+        let report = Uncertainty::default().monte_carlo(&road_network, &solution, 5, 0);
+        assert_eq!(report.makespans, vec![Time(37); 5]);
+        assert_eq!(report.capacity_violation_probability(), 0.0);
+
+        let uncertainty = Uncertainty {
+            lengths: Distribution::Uniform { min: 1.0, max: 3.0 },
+            capacities: Distribution::Uniform { min: 0.5, max: 1.0 },
+        };
+        let report = uncertainty.monte_carlo(&road_network, &solution, 50, 42);
+        assert_eq!(
+            report,
+            uncertainty.monte_carlo(&road_network, &solution, 50, 42)
+        );
+        assert_eq!(report.makespans.len(), 50);
+        assert!(report.quantile(0.0) <= report.quantile(0.9));
+        assert!(report.mean_makespan() > 37.0);
+        assert!(report.capacity_violation_probability() > 0.0);
+        assert!(report.due_date_violation_probability() > 0.0);
+    }
+}
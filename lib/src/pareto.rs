@@ -0,0 +1,206 @@
+use crate::checker::{DueDateMode, EvacuationSolution, Objective};
+use crate::roads::RoadNetwork;
+use crate::solver::greedy;
+use crate::units::Time;
+
+/// Weights of the lateness tried by [`pareto_front`], soft due dates being
+/// tried with each of them on top of hard ones.
+const LATENESS_WEIGHTS: [f32; 6] = [0.0, 0.01, 0.1, 1.0, 10.0, 100.0];
+
+/// A solution of a Pareto front with its value for each objective of the
+/// front.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParetoPoint {
+    pub solution: EvacuationSolution,
+    pub values: Vec<f32>,
+}
+
+/// Set of solutions none of which is at least as good as another one on
+/// every objective.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParetoFront {
+    pub objectives: Vec<Objective>,
+    pub points: Vec<ParetoPoint>,
+}
+
+/// Tells whether `a` is at least as good as `b` on every objective.
+pub fn weakly_dominates(a: &[f32], b: &[f32]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a <= b)
+}
+
+/// Tells whether `a` is at least as good as `b` on every objective and
+/// better on one.
+pub fn dominates(a: &[f32], b: &[f32]) -> bool {
+    weakly_dominates(a, b) && a.iter().zip(b).any(|(a, b)| a < b)
+}
+
+impl ParetoFront {
+    pub fn new(objectives: &[Objective]) -> ParetoFront {
+        ParetoFront {
+            objectives: objectives.to_vec(),
+            points: vec![],
+        }
+    }
+
+    /// Adds a solution unless a point of the front is at least as good on
+    /// every objective, and removes the points it dominates.
+    ///
+    /// Returns whether the solution was added.
+    pub fn insert(&mut self, roads: &RoadNetwork, solution: EvacuationSolution) -> bool {
+        let values: Vec<f32> = self
+            .objectives
+            .iter()
+            .map(|objective| solution.objective(roads, *objective))
+            .collect();
+
+        if self
+            .points
+            .iter()
+            .any(|point| weakly_dominates(&point.values, &values))
+        {
+            return false;
+        }
+        self.points
+            .retain(|point| !dominates(&values, &point.values));
+        self.points.push(ParetoPoint { solution, values });
+        true
+    }
+
+    /// Writes the objective vectors of the front as CSV, one line per point
+    /// by increasing values.
+    pub fn to_csv(&self) -> String {
+        let mut points: Vec<&ParetoPoint> = self.points.iter().collect();
+        points.sort_by(|a, b| a.values.partial_cmp(&b.values).unwrap());
+
+        let mut result = String::from("point");
+        for objective in &self.objectives {
+            result.push(',');
+            result.push_str(objective.name());
+        }
+        result.push('\n');
+
+        for (i, point) in points.iter().enumerate() {
+            result.push_str(i.to_string().as_str());
+            for value in &point.values {
+                result.push_str(format!(",{}", value).as_str());
+            }
+            result.push('\n');
+        }
+        result
+    }
+}
+
+/// Approximates the Pareto front of `objectives` by running the greedy
+/// solver on each of them, with hard due dates and with soft ones under
+/// increasing lateness weights, and keeping the non-dominated valid
+/// solutions. As the greedy solver follows the order of the evacuation nodes,
+/// it is also run on the nodes sorted by population, by route length and by
+/// due date.
+pub fn pareto_front(roads: &RoadNetwork, objectives: &[Objective]) -> ParetoFront {
+    let mut front = ParetoFront::new(objectives);
+    let modes: Vec<DueDateMode> = LATENESS_WEIGHTS
+        .iter()
+        .map(|weight| DueDateMode::Soft { weight: *weight })
+        .chain(std::iter::once(DueDateMode::Hard))
+        .collect();
+
+    for ordered in node_orders(roads) {
+        for due_dates in &modes {
+            for objective in objectives {
+                let solution = greedy(&ordered, *objective, *due_dates);
+
+                if solution.valid {
+                    front.insert(roads, solution);
+                }
+            }
+        }
+    }
+    front
+}
+
+/// Copies of the network with the evacuation nodes in the orders tried by
+/// [`pareto_front`].
+fn node_orders(roads: &RoadNetwork) -> Vec<RoadNetwork> {
+    let route = |id: u32| roads.get_route_edges(id);
+    let length = |id: u32| route(id).iter().map(|e| e.length).sum::<Time>();
+    let due_date = |id: u32| route(id).iter().map(|e| e.due_date).min();
+
+    let mut result = vec![roads.clone(); 4];
+    result[1]
+        .evac_info
+        .nodes
+        .sort_by_key(|node| std::cmp::Reverse(node.population));
+    result[2]
+        .evac_info
+        .nodes
+        .sort_by_key(|node| length(node.id));
+    result[3]
+        .evac_info
+        .nodes
+        .sort_by_key(|node| due_date(node.id));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::{EvacuationSolution, Objective};
+    use crate::evac::NodeCapacity;
+    use crate::pareto::{dominates, pareto_front, ParetoFront};
+    use crate::tests::{example_network, example_solution};
+    use crate::units::Rate;
+
+    #[test]
+    fn test_dominates() {
+        assert!(dominates(&[1.0, 2.0], &[1.0, 3.0]));
+        assert!(!dominates(&[1.0, 2.0], &[1.0, 2.0]));
+        assert!(!dominates(&[1.0, 4.0], &[2.0, 3.0]));
+    }
+
+    #[test]
+    fn test_insert() {
+        let road_network = example_network();
+        let mut front = ParetoFront::new(&[Objective::Makespan, Objective::TotalPersonTime]);
+
+        let mut late = EvacuationSolution::new("late");
+        late.add_node(1, 5, 10);
+        late.add_node(2, 3, 10);
+        late.add_node(3, 3, 10);
+        assert!(front.insert(&road_network, late));
+
+        let solution = example_solution();
+        assert!(front.insert(&road_network, solution.clone()));
+        assert_eq!(front.points.len(), 1);
+        assert_eq!(front.points[0].values, vec![37.0, 3195.0]);
+
+        assert!(!front.insert(&road_network, solution));
+        assert_eq!(
+            front.to_csv(),
+            "point,makespan,total_person_time\n0,37,3195\n"
+        );
+    }
+
+    #[test]
+    fn test_pareto_front() {
+        // A small shelter forces a trade-off between the makespan and the
+        // lateness on 12 -> 13.
+        let mut road_network = example_network();
+        road_network.evac_info.node_capacities.insert(
+            13,
+            NodeCapacity {
+                intake: Some(Rate(4)),
+                occupancy: None,
+            },
+        );
+        let objectives = [Objective::Makespan, Objective::TotalLateness];
+        let front = pareto_front(&road_network, &objectives);
+
+        assert!(!front.points.is_empty());
+        for a in &front.points {
+            assert!(a.solution.check(&road_network));
+            for b in &front.points {
+                assert!(!dominates(&a.values, &b.values));
+            }
+        }
+        assert_eq!(front.to_csv().lines().count(), front.points.len() + 1);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::checker::EvacuationSolution;
+use crate::roads::RoadNetwork;
+use crate::units::{Population, Time};
+
+const WIDTH: f32 = 1000.0;
+const MARGIN: f32 = 80.0;
+const ROW_HEIGHT: f32 = 20.0;
+const LOAD_HEIGHT: f32 = 40.0;
+
+/// Draws an evacuation plan as an SVG timeline.
+///
+/// The upper panel has one row per evacuation node with its departure window
+/// (blue) and its arrival window at the safe node (green). The lower panel
+/// shows the load of every route edge over time against its capacity (dashed
+/// red line).
+pub fn plan_to_svg(roads: &RoadNetwork, solution: &EvacuationSolution) -> String {
+    let schedules = solution.schedules(roads);
+    let profile = solution.flow_profile(roads);
+
+    let mut edges: Vec<_> = profile.edges.iter().collect();
+    edges.sort_by_key(|(key, _)| **key);
+
+    let horizon = schedules
+        .iter()
+        .map(|s| s.arrival.1)
+        .chain(
+            edges
+                .iter()
+                .filter_map(|(_, load)| load.span())
+                .map(|(_, end)| end),
+        )
+        .max()
+        .unwrap_or_default()
+        .max(Time(1));
+    let scale = (WIDTH - 2.0 * MARGIN) / horizon.0 as f32;
+    let x = |t: Time| MARGIN + t.0 as f32 * scale;
+
+    let load_top = MARGIN + (schedules.len() as f32 + 1.0) * ROW_HEIGHT;
+    let height = load_top + edges.len() as f32 * (LOAD_HEIGHT + 10.0) + MARGIN;
+
+    let mut result = String::with_capacity(10000);
+    result.push_str(
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"10\">\n",
+            WIDTH, height
+        )
+        .as_str(),
+    );
+
+    // Time axis
+    let step = Time((horizon.0 / 10).max(1));
+    let mut t = Time::ZERO;
+    while t <= horizon {
+        result.push_str(
+            format!(
+                "<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"#ddd\"/>\n<text x=\"{0}\" y=\"{3}\" text-anchor=\"middle\">{4}</text>\n",
+                x(t),
+                MARGIN - 10.0,
+                height - MARGIN,
+                MARGIN - 15.0,
+                t
+            )
+            .as_str(),
+        );
+        if t == Time::MAX {
+            break;
+        }
+        t += step;
+    }
+
+    // Departure and arrival windows
+    for (i, schedule) in schedules.iter().enumerate() {
+        let y = MARGIN + i as f32 * ROW_HEIGHT;
+        result.push_str(
+            format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">node {}</text>\n",
+                MARGIN - 5.0,
+                y + ROW_HEIGHT * 0.7,
+                schedule.id
+            )
+            .as_str(),
+        );
+
+        for &((start, end), color) in &[
+            (schedule.departure, "steelblue"),
+            (schedule.arrival, "seagreen"),
+        ] {
+            result.push_str(
+                format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"0.7\"/>\n",
+                    x(start),
+                    y + 2.0,
+                    (end - start).0 as f32 * scale,
+                    ROW_HEIGHT - 4.0,
+                    color
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    // Edge loads
+    for (i, ((parent, child), load)) in edges.iter().enumerate() {
+        let bottom = load_top + (i as f32 + 1.0) * (LOAD_HEIGHT + 10.0);
+        let max = load
+            .capacity_changes
+            .iter()
+            .map(|change| change.capacity.during(Time(1)))
+            .fold(load.peak().max(load.capacity), Population::max)
+            .0
+            .max(1) as f32;
+        let y = |value: Population| bottom - value.0 as f32 / max * LOAD_HEIGHT;
+
+        result.push_str(
+            format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{} -> {}</text>\n",
+                MARGIN - 5.0,
+                bottom - LOAD_HEIGHT / 2.0,
+                parent,
+                child
+            )
+            .as_str(),
+        );
+
+        let mut points = format!("{},{}", x(Time::ZERO), bottom);
+        let mut last = Time::ZERO;
+        for (t, value) in load.counts() {
+            if t > last {
+                points.push_str(format!(" {},{} {},{}", x(last), bottom, x(t), bottom).as_str());
+            }
+            points.push_str(
+                format!(" {},{} {},{}", x(t), y(value), x(t + Time(1)), y(value)).as_str(),
+            );
+            last = t + Time(1);
+        }
+        points.push_str(format!(" {},{}", x(last), bottom).as_str());
+
+        result.push_str(
+            format!(
+                "<polyline points=\"{}\" fill=\"steelblue\" fill-opacity=\"0.4\" stroke=\"steelblue\"/>\n",
+                points
+            )
+            .as_str(),
+        );
+        let mut dates = vec![Time::ZERO];
+        dates.extend(
+            load.capacity_changes
+                .iter()
+                .map(|change| change.date.min(horizon)),
+        );
+        dates.push(horizon);
+        for step in dates.windows(2) {
+            result.push_str(
+                format!(
+                    "<line x1=\"{}\" y1=\"{2}\" x2=\"{}\" y2=\"{2}\" stroke=\"red\" stroke-dasharray=\"4\"/>\n",
+                    x(step[0]),
+                    x(step[1]),
+                    y(load.capacity_at(step[0]))
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    result.push_str("</svg>\n");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plot::plan_to_svg;
+    use crate::tests::{example_network, example_solution};
+    use crate::units::Rate;
+
+    #[test]
+    fn test_plan_to_svg() {
+        let road_network = example_network();
+        let mut solution = example_solution();
+
+        let svg = plan_to_svg(&road_network, &solution);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 6);
+        assert_eq!(svg.matches("<polyline").count(), 5);
+        assert!(svg.contains(">node 3</text>"));
+        assert!(svg.contains(">12 -> 13</text>"));
+
+        // Closed roads nobody takes still have a scale
+        let mut road_network = road_network;
+        road_network
+            .edges_mut()
+            .for_each(|edge| edge.capacity = Rate(0));
+        for node in &mut solution.nodes {
+            node.evacuation_rate = Rate(0);
+        }
+        let svg = plan_to_svg(&road_network, &solution);
+        assert!(svg.contains(">12 -> 13</text>"));
+        assert!(!svg.contains("NaN"));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::checker::{DueDateMode, EvacuationSolution, Objective};
+use crate::roads::RoadNetwork;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Solver run by a worker of a portfolio: builds a solution from a seed,
+/// possibly starting from the best valid solution found so far by the
+/// portfolio.
+pub type Solver<'a> =
+    dyn Fn(&RoadNetwork, u64, Option<&EvacuationSolution>) -> EvacuationSolution + Sync + 'a;
+
+/// Solver run over and over in its own thread.
+pub struct Worker<'a> {
+    pub name: String,
+    /// Seed of the first run, the next runs taking the following seeds.
+    pub seed: u64,
+    /// Number of runs after which the worker stops, `None` to keep going
+    /// until the time budget is spent. A deterministic solver needs a single
+    /// run.
+    pub max_runs: Option<usize>,
+    pub solver: Box<Solver<'a>>,
+}
+
+/// What a worker did during a portfolio run.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct WorkerStats {
+    pub name: String,
+    pub runs: usize,
+    /// Runs whose solution passed the checker.
+    pub valid_runs: usize,
+    /// Best goal value among the valid solutions of the worker.
+    pub best_goal: Option<f32>,
+    /// Number of times the worker improved the best solution of the
+    /// portfolio.
+    pub improvements: usize,
+    /// compute time (expressed in seconds)
+    pub compute_time: f32,
+}
+
+/// Outcome of a portfolio run.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PortfolioReport {
+    /// Best valid solution found, `None` when no worker found any.
+    pub best: Option<EvacuationSolution>,
+    /// Statistics of the workers, in the order they were given.
+    pub workers: Vec<WorkerStats>,
+}
+
+impl<'a> Worker<'a> {
+    pub fn new<F>(name: &str, seed: u64, max_runs: Option<usize>, solver: F) -> Worker<'a>
+    where
+        F: Fn(&RoadNetwork, u64, Option<&EvacuationSolution>) -> EvacuationSolution + Sync + 'a,
+    {
+        Worker {
+            name: String::from(name),
+            seed,
+            max_runs,
+            solver: Box::new(solver),
+        }
+    }
+
+    fn run(
+        &self,
+        roads: &RoadNetwork,
+        best: &Mutex<Option<EvacuationSolution>>,
+        objective: Objective,
+        due_dates: DueDateMode,
+        deadline: Instant,
+    ) -> WorkerStats {
+        let earlier = Instant::now();
+        let mut stats = WorkerStats {
+            name: self.name.clone(),
+            ..WorkerStats::default()
+        };
+
+        while stats.runs == 0
+            || Instant::now() < deadline && self.max_runs.is_none_or(|max| stats.runs < max)
+        {
+            let start = best.lock().unwrap().clone();
+            let seed = self.seed.wrapping_add(stats.runs as u64);
+            let mut solution = (self.solver)(roads, seed, start.as_ref());
+            stats.runs += 1;
+
+            solution.valid = solution.check_due_dates(roads, due_dates);
+            if !solution.valid {
+                continue;
+            }
+            solution.goal_value = solution.goal(roads, objective, due_dates);
+            stats.valid_runs += 1;
+            if stats
+                .best_goal
+                .is_none_or(|goal| solution.goal_value < goal)
+            {
+                stats.best_goal = Some(solution.goal_value);
+            }
+
+            let mut best = best.lock().unwrap();
+            if best
+                .as_ref()
+                .is_none_or(|best| solution.goal_value < best.goal_value)
+            {
+                *best = Some(solution);
+                stats.improvements += 1;
+            }
+        }
+
+        stats.compute_time = earlier.elapsed().as_secs_f32();
+        stats
+    }
+}
+
+/// Runs each worker in its own thread over the same network, until the
+/// time budget is spent or every worker did its runs, and keeps the valid
+/// solution with the best goal for `objective`.
+///
+/// The solutions are checked with [`EvacuationSolution::check_due_dates`]
+/// and valued with [`EvacuationSolution::goal`]. Every worker does at least
+/// one run, and a run is never interrupted: the budget only stops the workers
+/// from starting new runs.
+pub fn portfolio(
+    roads: &RoadNetwork,
+    workers: &[Worker],
+    objective: Objective,
+    due_dates: DueDateMode,
+    budget: Duration,
+) -> PortfolioReport {
+    let deadline = Instant::now() + budget;
+    let best = Mutex::new(None);
+
+    let workers = thread::scope(|scope| {
+        let handles: Vec<_> = workers
+            .iter()
+            .map(|worker| {
+                let best = &best;
+                scope.spawn(move || worker.run(roads, best, objective, due_dates, deadline))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    PortfolioReport {
+        best: best.into_inner().unwrap(),
+        workers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::{DueDateMode, EvacuationSolution, Objective};
+    use crate::portfolio::{portfolio, Worker};
+    use crate::solver::{greedy, shuffled_greedy};
+    use crate::tests::example_network;
+    use std::time::Duration;
+
+    #[test]
+    fn test_portfolio() {
+        let road_network = example_network();
+        let (objective, due_dates) = (Objective::TotalPersonTime, DueDateMode::Hard);
+
+        let workers = [
+            Worker::new("greedy", 0, Some(1), |roads, _, _| {
+                greedy(roads, objective, due_dates)
+            }),
+            Worker::new("shuffled", 0, Some(6), |roads, seed, _| {
+                shuffled_greedy(roads, objective, due_dates, seed)
+            }),
+            // Only gives back the best solution, once there is one, an
+            // empty one before.
+            Worker::new("copy", 0, Some(3), |_, _, best| {
+                best.cloned()
+                    .unwrap_or_else(|| EvacuationSolution::new("empty"))
+            }),
+        ];
+        let report = portfolio(
+            &road_network,
+            &workers,
+            objective,
+            due_dates,
+            Duration::from_secs(60),
+        );
+
+        let best = report.best.unwrap();
+        assert!(!best.nodes.is_empty());
+        assert!(best.valid);
+        assert!(best.check(&road_network));
+        assert_eq!(
+            best.goal_value,
+            best.goal(&road_network, objective, due_dates)
+        );
+        let greedy_goal = greedy(&road_network, objective, due_dates).goal_value;
+        assert!(best.goal_value <= greedy_goal);
+
+        let runs: Vec<usize> = report.workers.iter().map(|w| w.runs).collect();
+        assert_eq!(runs, vec![1, 6, 3]);
+        assert_eq!(report.workers[0].best_goal, Some(greedy_goal));
+        assert_eq!(
+            report
+                .workers
+                .iter()
+                .filter_map(|w| w.best_goal)
+                .fold(f32::MAX, f32::min),
+            best.goal_value
+        );
+        assert!(report.workers.iter().map(|w| w.improvements).sum::<usize>() >= 1);
+        // Copies never improve the best solution.
+        assert_eq!(report.workers[2].improvements, 0);
+
+        // Without budget, each worker still runs once.
+        let report = portfolio(
+            &road_network,
+            &workers[..2],
+            objective,
+            due_dates,
+            Duration::ZERO,
+        );
+        assert!(report.best.is_some());
+        assert!(report.workers.iter().all(|w| w.runs == 1));
+
+        // The empty solution evacuates nobody, so it is never kept.
+        let report = portfolio(
+            &road_network,
+            &workers[2..],
+            objective,
+            due_dates,
+            Duration::ZERO,
+        );
+        assert_eq!(report.best, None);
+        assert_eq!(report.workers[0].valid_runs, 0);
+    }
+}
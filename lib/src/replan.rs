@@ -0,0 +1,234 @@
+use crate::checker::{DueDateMode, EvacuationSolution, Objective};
+use crate::roads::{CapacityChange, RoadNetwork};
+use crate::solver::{schedule, Stream};
+use crate::units::{Population, Rate, Time};
+use std::collections::HashMap;
+
+/// What is known of an evacuation in progress at date `time`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ObservedState {
+    pub time: Time,
+    /// Number of people who already left each evacuation node, nodes missing
+    /// from the map having nobody gone.
+    pub departed: HashMap<u32, Population>,
+    /// Roads closed from `time` on, in both orientations.
+    pub closed_edges: Vec<(u32, u32)>,
+}
+
+impl ObservedState {
+    /// Copy of the network where the closed roads have a null capacity from
+    /// `time` on.
+    pub fn apply(&self, roads: &RoadNetwork) -> RoadNetwork {
+        let mut result = roads.clone();
+
+        for edge in result.edges_mut() {
+            let closed = self.closed_edges.iter().any(|(a, b)| {
+                (edge.parent, edge.child) == (*a, *b) || (edge.parent, edge.child) == (*b, *a)
+            });
+            if closed {
+                let time = self.time;
+                edge.capacity_changes.retain(|change| change.date < time);
+                edge.capacity_changes.push(CapacityChange {
+                    date: time,
+                    capacity: Rate::ZERO,
+                });
+            }
+        }
+        result
+    }
+}
+
+/// Builds a new plan for the people still at their evacuation node.
+///
+/// The people who already left are assumed to have followed `plan`: they
+/// keep its streams, in the order of the plan, with their share of the
+/// departed people. Everyone else is scheduled from `state.time` on by the
+/// greedy solver, around them, on the first route of their node avoiding the
+/// closed roads. The result is to be checked against
+/// [`ObservedState::apply`]; it is invalid when evacuees already on their
+/// way meet a closed road.
+pub fn replan(
+    roads: &RoadNetwork,
+    plan: &EvacuationSolution,
+    state: &ObservedState,
+    objective: Objective,
+    due_dates: DueDateMode,
+) -> EvacuationSolution {
+    let network = state.apply(roads);
+    let mut solution = EvacuationSolution::new("replan");
+    let departed = |id: u32| {
+        let population = roads.evac_info.get_evacuation_data(id).unwrap().population;
+        state
+            .departed
+            .get(&id)
+            .copied()
+            .unwrap_or(Population::ZERO)
+            .min(population)
+    };
+
+    // Departed people not yet assigned to a stream of the plan.
+    let mut unassigned: HashMap<u32, Population> = roads
+        .evac_info
+        .nodes
+        .iter()
+        .map(|evac_node| (evac_node.id, departed(evac_node.id)))
+        .collect();
+
+    for sol_node in &plan.nodes {
+        let (population, left) = match (
+            roads.evac_info.get_evacuation_data(sol_node.id()),
+            unassigned.get_mut(&sol_node.id()),
+        ) {
+            (Some(evac_node), Some(left)) => (evac_node.population, left),
+            _ => continue,
+        };
+        let share = sol_node.stream_population(population).min(*left);
+
+        if share > Population::ZERO {
+            let mut committed = sol_node.clone();
+            committed.share = Some(share);
+            solution.nodes.push(committed);
+            *left -= share;
+        }
+    }
+
+    let streams = roads
+        .evac_info
+        .nodes
+        .iter()
+        .filter_map(|evac_node| {
+            let departed = departed(evac_node.id);
+            if departed == evac_node.population {
+                return None;
+            }
+
+            let open = |route: &usize| {
+                let edges = network.get_route_edges_for(evac_node.id, *route);
+                edges
+                    .last()
+                    .is_some_and(|e| network.evac_info.is_safe(e.child))
+                    && !edges.iter().any(|e| e.is_closed_from(state.time))
+            };
+            let route = (0..roads.evac_info.get_routes(evac_node.id).len())
+                .find(open)
+                .unwrap_or(0);
+
+            Some(Stream {
+                id: evac_node.id,
+                route,
+                share: Some(evac_node.population - departed),
+                release: state.time,
+            })
+        })
+        .collect();
+
+    schedule(&network, solution, streams, objective, due_dates)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::{DueDateMode, Objective};
+    use crate::replan::{replan, ObservedState};
+    use crate::roads::RoadEdge;
+    use crate::tests::{example_network, example_solution};
+    use crate::units::{DueDate, Population, Rate, Time};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_replan() {
+        let road_network = example_network();
+        let plan = example_solution();
+        // Node 2 left late, node 3 did not leave yet.
+        let state = ObservedState {
+            time: Time(5),
+            departed: HashMap::from([(1, Population(25)), (2, Population(6))]),
+            closed_edges: vec![],
+        };
+        let solution = replan(
+            &road_network,
+            &plan,
+            &state,
+            Objective::Makespan,
+            DueDateMode::Soft { weight: 0.0 },
+        );
+
+        assert!(solution.valid);
+        assert!(solution.check(&state.apply(&road_network)));
+        let shares: Vec<_> = solution.nodes.iter().map(|n| (n.id(), n.share)).collect();
+        assert_eq!(
+            shares,
+            vec![
+                (1, Some(Population(25))),
+                (2, Some(Population(6))),
+                (1, Some(Population(23))),
+                (2, Some(Population(24))),
+                (3, Some(Population(33)))
+            ]
+        );
+        for sol_node in &solution.nodes[2..] {
+            assert!(sol_node.start_date >= Time(5));
+        }
+    }
+
+    #[test]
+    fn test_replan_closed_edge() {
+        let mut road_network = example_network();
+        let plan = example_solution();
+        road_network
+            .evac_info
+            .alternative_routes
+            .insert(2, vec![vec![11, 13]]);
+        road_network.insert_edge(RoadEdge {
+            parent: 11,
+            child: 13,
+            due_date: DueDate::At(Time(100)),
+            length: Time(30),
+            capacity: Rate(10),
+            capacity_changes: vec![],
+        });
+
+        // Node 1 is gone, and its last evacuees entered 11 -> 12 at 16.
+        let state = ObservedState {
+            time: Time(17),
+            departed: HashMap::from([
+                (1, Population(48)),
+                (2, Population(30)),
+                (3, Population(12)),
+            ]),
+            closed_edges: vec![(12, 11)],
+        };
+        let network = state.apply(&road_network);
+        assert_eq!(
+            network.get_edge(11, 12).unwrap().capacity_at(Time(17)),
+            Rate::ZERO
+        );
+
+        let solution = replan(
+            &road_network,
+            &plan,
+            &state,
+            Objective::Makespan,
+            DueDateMode::Soft { weight: 0.0 },
+        );
+        assert!(solution.valid);
+        assert!(solution.check(&network));
+        assert_eq!(solution.nodes.len(), 4);
+        assert_eq!(solution.nodes[3].id(), 3);
+
+        // Node 2 has to take the detour.
+        let state = ObservedState {
+            departed: HashMap::from([(1, Population(48)), (3, Population(12))]),
+            ..state
+        };
+        let solution = replan(
+            &road_network,
+            &plan,
+            &state,
+            Objective::Makespan,
+            DueDateMode::Soft { weight: 0.0 },
+        );
+        assert!(solution.check(&state.apply(&road_network)));
+        let detour = solution.nodes.iter().find(|n| n.id() == 2).unwrap();
+        assert_eq!(detour.route, 1);
+    }
+}
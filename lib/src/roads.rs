@@ -1,23 +1,76 @@
-use crate::evac::EvacuationInfo;
+use crate::checker::EvacuationSolution;
+use crate::evac::{parse, read_section, EvacuationInfo, EvacuationNode};
+use crate::units::{DueDate, Rate, Time};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RoadNetwork {
     pub evac_info: EvacuationInfo,
     /// This map associates a node to a Vec containing the ids of
     /// the edges connected to this node.
-    nodes: HashMap<u32, Vec<u32>>,
-    edges: HashMap<u32, RoadEdge>,
+    pub(crate) nodes: HashMap<u32, Vec<u32>>,
+    pub(crate) edges: HashMap<u32, RoadEdge>,
+    /// Roads whose opposite lanes can be reversed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub contraflows: Vec<Contraflow>,
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RoadEdge {
     pub parent: u32,
     pub child: u32,
-    pub due_date: u64,
-    pub length: u32,
-    pub capacity: u32,
+    pub due_date: DueDate,
+    pub length: Time,
+    pub capacity: Rate,
+    /// Changes of the capacity over time, by increasing dates. The edge has
+    /// `capacity` until the first change, a null capacity closes it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub capacity_changes: Vec<CapacityChange>,
+}
+
+/// Capacity of an edge from `date` on.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CapacityChange {
+    pub date: Time,
+    pub capacity: Rate,
+}
+
+/// Road whose lanes in the direction unused by the evacuation can be
+/// reversed, doubling the capacity of the other direction.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Contraflow {
+    pub node1: u32,
+    pub node2: u32,
+    pub cost: u32,
+    /// Date from which the reversed lanes can be used.
+    pub delay: Time,
+}
+
+/// Read access to a road network, as needed to evaluate solutions on it.
+///
+/// Implemented by [`RoadNetwork`] and by its compiled form
+/// [`CompactNetwork`](crate::compact::CompactNetwork), which answers the
+/// same queries without scanning or cloning.
+pub trait Network {
+    fn evac_info(&self) -> &EvacuationInfo;
+
+    /// Returns the evacuation node with id `node_id`.
+    fn evacuation_node(&self, node_id: u32) -> Option<&EvacuationNode>;
+
+    /// Returns the edge going from `parent` to `child`.
+    fn edge(&self, parent: u32, child: u32) -> Option<&RoadEdge>;
+
+    /// Returns the edges of one of the escape routes of a node, see
+    /// [`RoadNetwork::get_route_edges_for`].
+    fn route_edges(&self, node_id: u32, route_index: usize) -> Cow<'_, [RoadEdge]>;
 }
 
 enum ParsingState {
@@ -27,12 +80,33 @@ enum ParsingState {
     End,
 }
 
+impl RoadEdge {
+    /// Capacity of the edge at `date`.
+    pub fn capacity_at(&self, date: Time) -> Rate {
+        self.capacity_changes
+            .iter()
+            .take_while(|change| change.date <= date)
+            .last()
+            .map_or(self.capacity, |change| change.capacity)
+    }
+
+    /// Tells whether the edge is closed from `date` on, for good.
+    pub fn is_closed_from(&self, date: Time) -> bool {
+        self.capacity_at(date) == Rate::ZERO
+            && self
+                .capacity_changes
+                .iter()
+                .all(|change| change.date <= date || change.capacity == Rate::ZERO)
+    }
+}
+
 impl RoadNetwork {
     pub fn new(evac_info: EvacuationInfo) -> RoadNetwork {
         RoadNetwork {
             evac_info,
             nodes: HashMap::new(),
             edges: HashMap::new(),
+            contraflows: vec![],
         }
     }
 
@@ -51,6 +125,7 @@ impl RoadNetwork {
             evac_info,
             nodes: HashMap::new(),
             edges: HashMap::new(),
+            contraflows: vec![],
         };
 
         for line in filestr.lines() {
@@ -71,16 +146,23 @@ impl RoadNetwork {
                     };
                 }
                 ParsingState::Road => {
-                    if let Some((parent, child)) = result.evac_info.get_edge(
+                    let orientations = result.evac_info.get_edge_orientations(
                         words[0].parse::<u32>().unwrap(),
                         words[1].parse::<u32>().unwrap(),
-                    ) {
+                    );
+
+                    for (parent, child) in orientations {
+                        // Both orientations of a road come from its first line.
+                        if result.edge(parent, child).is_some() {
+                            continue;
+                        }
                         let edge = RoadEdge {
                             parent,
                             child,
-                            due_date: words[2].parse::<u64>().unwrap(),
-                            length: words[3].parse::<u32>().unwrap(),
-                            capacity: words[4].parse::<u32>().unwrap(),
+                            due_date: words[2].parse().unwrap(),
+                            length: words[3].parse().unwrap(),
+                            capacity: words[4].parse().unwrap(),
+                            capacity_changes: vec![],
                         };
                         result.add_edge_reference(edge.parent, key);
                         result.add_edge_reference(edge.child, key);
@@ -97,10 +179,199 @@ impl RoadNetwork {
             }
         }
 
-        match parsing {
-            ParsingState::End => Ok(result),
-            _ => Err("Error while parsing"),
+        if !matches!(parsing, ParsingState::End) {
+            return Err("Error while parsing");
+        }
+
+        if let Some(rows) = read_section(filestr, "c [capacity changes]", 3)? {
+            for words in rows {
+                let (n1, n2) = (parse::<u32>(words[0])?, parse::<u32>(words[1])?);
+                let count = parse::<usize>(words[2])?;
+
+                if words.len() < 3 + 2 * count {
+                    return Err("Error while parsing");
+                }
+                let mut changes = Vec::with_capacity(count);
+                for pair in words[3..3 + 2 * count].chunks(2) {
+                    changes.push(CapacityChange {
+                        date: parse(pair[0])?,
+                        capacity: parse(pair[1])?,
+                    });
+                }
+
+                for edge in result.edges.values_mut() {
+                    if (edge.parent, edge.child) == (n1, n2)
+                        || (edge.parent, edge.child) == (n2, n1)
+                    {
+                        edge.capacity_changes = changes.clone();
+                    }
+                }
+            }
+        }
+
+        if let Some(rows) = read_section(filestr, "c [contraflow]", 4)? {
+            for words in rows {
+                result.contraflows.push(Contraflow {
+                    node1: parse(words[0])?,
+                    node2: parse(words[1])?,
+                    cost: parse(words[2])?,
+                    delay: parse(words[3])?,
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Write RoadNetwork in the `.full` file format, evacuation info included.
+    ///
+    /// Only the edges used by escape routes are known, so the graph section
+    /// contains just those, a road used in both directions being written
+    /// once.
+    pub fn to_file(&self) -> String {
+        let mut result = self.evac_info.to_file();
+        result.push_str("c [graph] format: header with <num nodes> <num edges> then one line per edge <node 1> <node 2> <duedate> <length> <capacity>\n");
+
+        let mut keys: Vec<&u32> = self.edges.keys().collect();
+        keys.sort();
+
+        let mut roads: Vec<&RoadEdge> = vec![];
+        for key in keys {
+            let edge = &self.edges[key];
+            if !roads
+                .iter()
+                .any(|e| (e.parent, e.child) == (edge.child, edge.parent))
+            {
+                roads.push(edge);
+            }
+        }
+
+        result.push_str(format!("{} {}\n", self.nodes.len(), roads.len()).as_str());
+        for edge in roads {
+            result.push_str(
+                format!(
+                    "{} {} {} {} {}\n",
+                    edge.parent, edge.child, edge.due_date, edge.length, edge.capacity
+                )
+                .as_str(),
+            );
+        }
+
+        let mut changed: Vec<&RoadEdge> = vec![];
+        for edge in self.edges.values() {
+            let known = changed
+                .iter()
+                .any(|e| (e.parent, e.child) == (edge.child, edge.parent));
+            if !edge.capacity_changes.is_empty() && !known {
+                changed.push(edge);
+            }
+        }
+        changed.sort_by_key(|edge| (edge.parent, edge.child));
+
+        if !changed.is_empty() {
+            result.push_str("c [capacity changes] format: header with <num edges> then one line per edge <node 1> <node 2> <num changes> then <date> <capacity> for each change\n");
+            result.push_str(format!("{}\n", changed.len()).as_str());
+            for edge in changed {
+                result.push_str(
+                    format!(
+                        "{} {} {}",
+                        edge.parent,
+                        edge.child,
+                        edge.capacity_changes.len()
+                    )
+                    .as_str(),
+                );
+                for change in &edge.capacity_changes {
+                    result.push_str(format!(" {} {}", change.date, change.capacity).as_str());
+                }
+                result.push('\n');
+            }
+        }
+
+        if !self.contraflows.is_empty() {
+            result.push_str("c [contraflow] format: header with <num roads> then one line per road <node 1> <node 2> <cost> <delay>\n");
+            result.push_str(format!("{}\n", self.contraflows.len()).as_str());
+            for contraflow in &self.contraflows {
+                result.push_str(
+                    format!(
+                        "{} {} {} {}\n",
+                        contraflow.node1, contraflow.node2, contraflow.cost, contraflow.delay
+                    )
+                    .as_str(),
+                );
+            }
+        }
+
+        result
+    }
+
+    /// Render the network in Graphviz DOT format.
+    ///
+    /// Edges are oriented towards the safe node. When a solution is given,
+    /// they are coloured by their peak utilisation (peak load over capacity).
+    pub fn to_dot(&self, solution: Option<&EvacuationSolution>) -> String {
+        let profile = solution.map(|s| s.flow_profile(self));
+        let mut result = String::with_capacity(1000);
+        result.push_str("digraph evacuation {\n    rankdir=LR;\n");
+
+        let mut node_ids: Vec<&u32> = self.nodes.keys().collect();
+        node_ids.sort();
+
+        for id in node_ids {
+            let attributes = if self.evac_info.is_safe(*id) {
+                format!(
+                    "shape=doublecircle, style=filled, fillcolor=palegreen, label=\"{}\\nsafe\"",
+                    id
+                )
+            } else if let Some(node) = self.evac_info.get_evacuation_data(*id) {
+                format!(
+                    "shape=box, style=filled, fillcolor=lightblue, label=\"{}\\npop {}\\nrate {}\"",
+                    id, node.population, node.max_rate
+                )
+            } else {
+                String::from("shape=circle")
+            };
+            result.push_str(format!("    {} [{}];\n", id, attributes).as_str());
+        }
+
+        let mut keys: Vec<&u32> = self.edges.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let edge = &self.edges[key];
+            let mut label = format!(
+                "len {}\\ncap {}\\ndue {}",
+                edge.length, edge.capacity, edge.due_date
+            );
+            let mut color = "black";
+
+            if let Some(load) = profile
+                .as_ref()
+                .and_then(|p| p.edges.get(&(edge.parent, edge.child)))
+            {
+                let utilisation = load.utilisation();
+                label.push_str(format!("\\npeak {}", load.peak()).as_str());
+                color = if utilisation > 1.0 {
+                    "purple"
+                } else if utilisation >= 0.9 {
+                    "red"
+                } else if utilisation >= 0.5 {
+                    "orange"
+                } else {
+                    "forestgreen"
+                };
+            }
+            result.push_str(
+                format!(
+                    "    {} -> {} [label=\"{}\", color={}];\n",
+                    edge.parent, edge.child, label, color
+                )
+                .as_str(),
+            );
         }
+
+        result.push_str("}\n");
+        result
     }
 
     pub fn dump(&self) {
@@ -129,12 +400,138 @@ impl RoadNetwork {
         vec.push(edge);
     }
 
+    /// Iterates over the edges of the network, both orientations of a road
+    /// being two edges.
+    pub fn edges_mut(&mut self) -> impl Iterator<Item = &mut RoadEdge> {
+        self.edges.values_mut()
+    }
+
+    /// Reverses the lanes of a road listed in `contraflows`: the direction
+    /// used by the escape routes gets twice its capacity from the delay of
+    /// the road on. Fails when the road cannot be reversed or when both
+    /// directions are used.
+    pub fn reverse_lanes(&mut self, node1: u32, node2: u32) -> Result<(), &'static str> {
+        let road = |a: u32, b: u32| (a.min(b), a.max(b));
+        let delay = self
+            .contraflows
+            .iter()
+            .find(|c| road(c.node1, c.node2) == road(node1, node2))
+            .ok_or("Road cannot be reversed")?
+            .delay;
+
+        let mut edges: Vec<&mut RoadEdge> = self
+            .edges
+            .values_mut()
+            .filter(|edge| road(edge.parent, edge.child) == road(node1, node2))
+            .collect();
+        if edges.len() != 1 {
+            return Err("Both directions of the road are used");
+        }
+        let edge = &mut edges[0];
+
+        let mut changes: Vec<CapacityChange> = edge
+            .capacity_changes
+            .iter()
+            .filter(|change| change.date < delay)
+            .copied()
+            .collect();
+        changes.push(CapacityChange {
+            date: delay,
+            capacity: edge.capacity_at(delay) * 2,
+        });
+        changes.extend(
+            edge.capacity_changes
+                .iter()
+                .filter(|change| change.date > delay)
+                .map(|change| CapacityChange {
+                    date: change.date,
+                    capacity: change.capacity * 2,
+                }),
+        );
+        edge.capacity_changes = changes;
+        Ok(())
+    }
+
+    /// Adds an edge under the first unused key.
+    pub fn insert_edge(&mut self, edge: RoadEdge) {
+        let key = self.edges.keys().max().map_or(0, |key| key + 1);
+        self.add_road_edge(key, edge);
+    }
+
     pub fn add_road_edge(&mut self, key: u32, edge: RoadEdge) {
         self.add_edge_reference(edge.parent, key);
         self.add_edge_reference(edge.child, key);
         self.edges.insert(key, edge);
     }
 
+    /// Returns the edges of the escape route of an evacuation node, from the
+    /// node itself down to the first safe node met.
+    pub fn get_route_edges(&self, node_id: u32) -> Vec<RoadEdge> {
+        self.get_route_edges_for(node_id, 0)
+    }
+
+    /// Returns the edges of one of the escape routes of an evacuation node,
+    /// see [`EvacuationInfo::get_routes`]. The route stops at the first safe
+    /// node met, or before the first edge missing from the network.
+    ///
+    /// Nodes that are not evacuation nodes follow their child edges, see
+    /// [`get_child_path`](Self::get_child_path).
+    pub fn get_route_edges_for(&self, node_id: u32, route_index: usize) -> Vec<RoadEdge> {
+        let mut result = vec![];
+        let mut current = node_id;
+
+        if self.evac_info.get_evacuation_data(node_id).is_none() {
+            return self.get_child_path(node_id);
+        }
+
+        if let Some(route) = self.evac_info.get_routes(node_id).get(route_index) {
+            for next in route.iter() {
+                if self.evac_info.is_safe(current) {
+                    break;
+                }
+                match self.get_edge(current, *next) {
+                    Some(edge) => result.push(edge),
+                    None => break,
+                }
+                current = *next;
+            }
+        }
+        result
+    }
+
+    /// Returns the edge going from `parent` to `child`.
+    pub fn get_edge(&self, parent: u32, child: u32) -> Option<RoadEdge> {
+        self.edge(parent, child).cloned()
+    }
+
+    /// Returns the edges met from a node following child edges down to the
+    /// first safe node. Each node is left by its first edge towards a node
+    /// not met yet, so that a road used in both directions is not taken
+    /// back.
+    pub fn get_child_path(&self, node_id: u32) -> Vec<RoadEdge> {
+        let mut result = vec![];
+        let mut visited = HashSet::new();
+        let mut current = node_id;
+        visited.insert(current);
+
+        while !self.evac_info.is_safe(current) {
+            let next = self.nodes.get(&current).and_then(|keys| {
+                keys.iter()
+                    .map(|key| &self.edges[key])
+                    .find(|edge| edge.parent == current && !visited.contains(&edge.child))
+            });
+            match next {
+                Some(edge) => {
+                    current = edge.child;
+                    visited.insert(current);
+                    result.push(edge.clone());
+                }
+                None => break,
+            }
+        }
+        result
+    }
+
     pub fn get_child_edge(&self, node_id: u32) -> Option<RoadEdge> {
         if let Some(node) = self.nodes.get(&node_id) {
             for edge_id in node {
@@ -149,9 +546,120 @@ impl RoadNetwork {
     }
 }
 
+impl Network for RoadNetwork {
+    fn evac_info(&self) -> &EvacuationInfo {
+        &self.evac_info
+    }
+
+    fn evacuation_node(&self, node_id: u32) -> Option<&EvacuationNode> {
+        self.evac_info.get_evacuation_data(node_id)
+    }
+
+    fn edge(&self, parent: u32, child: u32) -> Option<&RoadEdge> {
+        self.nodes
+            .get(&parent)?
+            .iter()
+            .map(|edge_id| &self.edges[edge_id])
+            .find(|edge| edge.parent == parent && edge.child == child)
+    }
+
+    fn route_edges(&self, node_id: u32, route_index: usize) -> Cow<'_, [RoadEdge]> {
+        Cow::Owned(self.get_route_edges_for(node_id, route_index))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::evac::EvacuationInfo;
+    use crate::roads::RoadNetwork;
+    use crate::tests::{example_network, example_solution, EXAMPLE};
+    use crate::units::{Rate, Time};
 
     #[test]
     fn test_get_chid_edge() {}
+
+    #[test]
+    fn test_get_route_edges() {
+        let road_network = example_network();
+
+        let route: Vec<(u32, u32)> = road_network
+            .get_route_edges(1)
+            .iter()
+            .map(|e| (e.parent, e.child))
+            .collect();
+        assert_eq!(route, vec![(1, 11), (11, 12), (12, 13)]);
+    }
+
+    #[test]
+    fn test_parsing_capacity_changes() {
+        let file_str = format!(
+            "{}c [capacity changes] blabla\n1\n13 12 2 40 5 80 11\n",
+            EXAMPLE
+        );
+        let road_network =
+            RoadNetwork::from_file(&file_str, EvacuationInfo::from_file(&file_str).unwrap())
+                .unwrap();
+
+        let edge = road_network.get_edge(12, 13).unwrap();
+        assert_eq!(edge.capacity_at(Time(39)), Rate(11));
+        assert_eq!(edge.capacity_at(Time(40)), Rate(5));
+        assert_eq!(edge.capacity_at(Time(80)), Rate(11));
+        assert_eq!(
+            road_network.get_edge(11, 12).unwrap().capacity_changes,
+            vec![]
+        );
+
+        let file = road_network.to_file();
+        assert!(file.contains("\n12 13 2 40 5 80 11\n"));
+        assert_eq!(
+            road_network,
+            RoadNetwork::from_file(&file, EvacuationInfo::from_file(&file).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_contraflow() {
+        let file_str = format!("{}c [contraflow]\n2\n13 12 5 10\n1 11 2 0\n", EXAMPLE);
+        let mut road_network =
+            RoadNetwork::from_file(&file_str, EvacuationInfo::from_file(&file_str).unwrap())
+                .unwrap();
+        assert_eq!(road_network.contraflows.len(), 2);
+        assert_eq!(road_network.contraflows[0].cost, 5);
+        assert_eq!(
+            road_network,
+            RoadNetwork::from_file(
+                &road_network.to_file(),
+                EvacuationInfo::from_file(&file_str).unwrap()
+            )
+            .unwrap()
+        );
+
+        road_network.reverse_lanes(12, 13).unwrap();
+        let edge = road_network.get_edge(12, 13).unwrap();
+        assert_eq!(edge.capacity_at(Time(9)), Rate(11));
+        assert_eq!(edge.capacity_at(Time(10)), Rate(22));
+        road_network.reverse_lanes(1, 11).unwrap();
+        assert_eq!(
+            road_network.get_edge(1, 11).unwrap().capacity_at(Time(0)),
+            Rate(16)
+        );
+        assert!(road_network.reverse_lanes(11, 12).is_err());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let road_network = example_network();
+        let solution = example_solution();
+
+        let dot = road_network.to_dot(None);
+        assert!(dot.starts_with("digraph evacuation {"));
+        assert!(dot.contains("13 [shape=doublecircle"));
+        assert!(dot.contains(
+            "1 [shape=box, style=filled, fillcolor=lightblue, label=\"1\\npop 48\\nrate 7\"]"
+        ));
+        assert!(dot.contains("11 -> 12 [label=\"len 9\\ncap 10\\ndue 33\", color=black]"));
+
+        let dot = road_network.to_dot(Some(&solution));
+        assert!(dot.contains("11 -> 12 [label=\"len 9\\ncap 10\\ndue 33\\npeak 8\", color=orange]"));
+    }
 }
@@ -0,0 +1,325 @@
+use crate::evac::{parse, EvacuationInfo};
+use crate::roads::{RoadEdge, RoadNetwork};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Full road graph of an instance. Roads can be taken both ways.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RoadGraph {
+    /// Every road of the `c [graph]` section, as written in the file.
+    pub edges: Vec<RoadEdge>,
+    adjacency: HashMap<u32, Vec<usize>>,
+}
+
+/// How the length of a route is measured when looking for alternatives.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RouteCost {
+    /// Travel time only.
+    Length,
+    /// Travel time plus the time needed to push the whole population of the
+    /// node through each edge, which steers the routes away from narrow roads.
+    CapacityAware,
+}
+
+impl RoadGraph {
+    pub fn new(edges: Vec<RoadEdge>) -> RoadGraph {
+        let mut adjacency: HashMap<u32, Vec<usize>> = HashMap::new();
+
+        for (i, edge) in edges.iter().enumerate() {
+            adjacency.entry(edge.parent).or_default().push(i);
+            adjacency.entry(edge.child).or_default().push(i);
+        }
+        RoadGraph { edges, adjacency }
+    }
+
+    /// Read the whole `c [graph]` section of a file, including the roads
+    /// which are not part of any escape route.
+    pub fn from_file(filestr: &str) -> Result<RoadGraph, &'static str> {
+        let mut lines = filestr
+            .lines()
+            .skip_while(|line| !line.starts_with("c [graph]"))
+            .skip(1);
+        let edge_count = lines
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .and_then(|word| word.parse::<usize>().ok())
+            .ok_or("Error while parsing")?;
+        let mut edges = vec![];
+
+        for line in lines.take(edge_count) {
+            let words: Vec<&str> = line.split(' ').collect();
+            if words.len() < 5 {
+                return Err("Error while parsing");
+            }
+            edges.push(RoadEdge {
+                parent: parse(words[0])?,
+                child: parse(words[1])?,
+                due_date: parse(words[2])?,
+                length: parse(words[3])?,
+                capacity: parse(words[4])?,
+                capacity_changes: vec![],
+            });
+        }
+
+        if edges.len() < edge_count {
+            return Err("Error while parsing");
+        }
+        Ok(RoadGraph::new(edges))
+    }
+
+    /// Returns the road between two nodes, oriented from `from` to `to`.
+    pub fn get_edge(&self, from: u32, to: u32) -> Option<RoadEdge> {
+        self.adjacency
+            .get(&from)?
+            .iter()
+            .map(|i| &self.edges[*i])
+            .find(|edge| edge.parent == to || edge.child == to)
+            .map(|edge| RoadEdge {
+                parent: from,
+                child: to,
+                ..edge.clone()
+            })
+    }
+
+    /// Shortest path from `from` to the closest safe node, avoiding the
+    /// given nodes and oriented edges. Returns its cost and its nodes, `from`
+    /// included.
+    fn shortest_path(
+        &self,
+        from: u32,
+        evac_info: &EvacuationInfo,
+        weight: &dyn Fn(&RoadEdge) -> u64,
+        banned_nodes: &HashSet<u32>,
+        banned_edges: &HashSet<(u32, u32)>,
+    ) -> Option<(u64, Vec<u32>)> {
+        let mut distances: HashMap<u32, u64> = HashMap::new();
+        let mut previous: HashMap<u32, u32> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        distances.insert(from, 0);
+        heap.push(Reverse((0, from)));
+
+        while let Some(Reverse((distance, node))) = heap.pop() {
+            if distance > distances[&node] {
+                continue;
+            }
+            if evac_info.is_safe(node) {
+                let mut path = vec![node];
+                while let Some(parent) = previous.get(path.last().unwrap()) {
+                    path.push(*parent);
+                }
+                path.reverse();
+                return Some((distance, path));
+            }
+
+            for i in self.adjacency.get(&node).into_iter().flatten() {
+                let edge = &self.edges[*i];
+                let next = if edge.parent == node {
+                    edge.child
+                } else {
+                    edge.parent
+                };
+                if banned_nodes.contains(&next) || banned_edges.contains(&(node, next)) {
+                    continue;
+                }
+
+                let candidate = distance + weight(edge);
+                if distances.get(&next).is_none_or(|d| candidate < *d) {
+                    distances.insert(next, candidate);
+                    previous.insert(next, node);
+                    heap.push(Reverse((candidate, next)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Computes up to `k` loopless escape routes from an evacuation node to
+    /// any safe node, shortest first (Yen's algorithm).
+    ///
+    /// Routes are given like [`EvacuationNode::route`](crate::evac::EvacuationNode::route),
+    /// without the node itself.
+    pub fn k_shortest_routes(
+        &self,
+        evac_info: &EvacuationInfo,
+        node_id: u32,
+        k: usize,
+        cost: RouteCost,
+    ) -> Vec<Vec<u32>> {
+        let population = evac_info
+            .get_evacuation_data(node_id)
+            .map_or(0, |node| node.population.0 as u64);
+        let weight = move |edge: &RoadEdge| -> u64 {
+            match cost {
+                RouteCost::Length => edge.length.0 as u64,
+                RouteCost::CapacityAware => {
+                    edge.length.0 as u64 + population / (edge.capacity.0 as u64).max(1)
+                }
+            }
+        };
+        let path_cost = |path: &[u32]| -> u64 {
+            path.windows(2)
+                .map(|w| weight(&self.get_edge(w[0], w[1]).unwrap()))
+                .sum()
+        };
+
+        let mut found: Vec<Vec<u32>> = vec![];
+        let mut candidates: Vec<(u64, Vec<u32>)> = vec![];
+
+        match self.shortest_path(
+            node_id,
+            evac_info,
+            &weight,
+            &HashSet::new(),
+            &HashSet::new(),
+        ) {
+            Some((_, path)) if k > 0 => found.push(path),
+            _ => return vec![],
+        }
+
+        while found.len() < k {
+            let last = found.last().unwrap().clone();
+
+            for i in 0..last.len() - 1 {
+                let root = &last[..=i];
+                let banned_nodes: HashSet<u32> = root[..i].iter().copied().collect();
+                let banned_edges: HashSet<(u32, u32)> = found
+                    .iter()
+                    .filter(|path| path.len() > i + 1 && &path[..=i] == root)
+                    .map(|path| (path[i], path[i + 1]))
+                    .collect();
+
+                if let Some((spur_cost, spur)) =
+                    self.shortest_path(last[i], evac_info, &weight, &banned_nodes, &banned_edges)
+                {
+                    let mut path = root[..i].to_vec();
+                    path.extend(spur);
+                    let total = path_cost(root) + spur_cost;
+
+                    if !found.contains(&path) && !candidates.iter().any(|(_, p)| *p == path) {
+                        candidates.push((total, path));
+                    }
+                }
+            }
+
+            if candidates.is_empty() {
+                break;
+            }
+            let best = (0..candidates.len())
+                .min_by_key(|i| (candidates[*i].0, candidates[*i].1.len()))
+                .unwrap();
+            found.push(candidates.remove(best).1);
+        }
+
+        found.into_iter().map(|path| path[1..].to_vec()).collect()
+    }
+
+    /// Gives every evacuation node of the network up to `k - 1` alternatives
+    /// to its escape route, and adds the edges they use to the network.
+    pub fn add_alternative_routes(&self, roads: &mut RoadNetwork, k: usize, cost: RouteCost) {
+        let ids: Vec<u32> = roads.evac_info.nodes.iter().map(|node| node.id).collect();
+
+        for id in ids {
+            let own_route = roads
+                .evac_info
+                .get_evacuation_data(id)
+                .unwrap()
+                .route
+                .clone();
+            let alternatives: Vec<Vec<u32>> = self
+                .k_shortest_routes(&roads.evac_info, id, k, cost)
+                .into_iter()
+                .filter(|route| *route != own_route)
+                .take(k.saturating_sub(1))
+                .collect();
+
+            for route in &alternatives {
+                let mut parent = id;
+                for child in route {
+                    if roads.get_edge(parent, *child).is_none() {
+                        roads.insert_edge(self.get_edge(parent, *child).unwrap());
+                    }
+                    parent = *child;
+                }
+            }
+
+            if !alternatives.is_empty() {
+                roads.evac_info.alternative_routes.insert(id, alternatives);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::EvacuationSolution;
+    use crate::evac::EvacuationInfo;
+    use crate::roads::RoadNetwork;
+    use crate::routes::{RoadGraph, RouteCost};
+    use crate::units::{Population, Rate, Time};
+
+    // Node 3 can also reach 13 through 11, and 12 through 14
+    const EXAMPLE: &str = "c [evacuation info]\n3 13\n1 48 7 3 11 12 13\n2 30 3 3 11 12 13\n3 33 3 2 12 13\nc [graph]\n8 8\n1 11 13 7 8\n2 11 26 4 5\n3 12 28 6 3\n11 12 33 9 10\n12 13 46 12 11\n3 11 40 3 20\n3 14 40 2 20\n14 12 40 5 20\n";
+
+    #[test]
+    fn test_k_shortest_routes() {
+        let evac_info = EvacuationInfo::from_file(EXAMPLE).unwrap();
+        let graph = RoadGraph::from_file(EXAMPLE).unwrap();
+        assert_eq!(graph.edges.len(), 8);
+
+        // Lengths and capacities must fit in 32 bits
+        let file = EXAMPLE.replace("14 12 40 5 20", "14 12 40 4294967301 20");
+        assert!(RoadGraph::from_file(&file).is_err());
+
+        let routes = graph.k_shortest_routes(&evac_info, 3, 4, RouteCost::Length);
+        assert_eq!(
+            routes,
+            vec![vec![12, 13], vec![14, 12, 13], vec![11, 12, 13]]
+        );
+
+        // Pushing 33 people through the capacity 3 road takes 11 time units
+        let routes = graph.k_shortest_routes(&evac_info, 3, 1, RouteCost::CapacityAware);
+        assert_eq!(routes, vec![vec![14, 12, 13]]);
+    }
+
+    #[test]
+    fn test_alternative_routes() {
+        let evac_info = EvacuationInfo::from_file(EXAMPLE).unwrap();
+        let mut road_network = RoadNetwork::from_file(EXAMPLE, evac_info).unwrap();
+        let graph = RoadGraph::from_file(EXAMPLE).unwrap();
+
+        graph.add_alternative_routes(&mut road_network, 2, RouteCost::Length);
+        assert_eq!(
+            road_network.evac_info.alternative_routes[&3],
+            vec![vec![14, 12, 13]]
+        );
+        assert_eq!(road_network.get_route_edges_for(3, 1).len(), 3);
+
+        // Alternative routes survive a round trip through the file format
+        let file = road_network.to_file();
+        let evac_info = EvacuationInfo::from_file(&file).unwrap();
+        assert_eq!(evac_info, road_network.evac_info);
+        assert_eq!(
+            RoadNetwork::from_file(&file, evac_info)
+                .unwrap()
+                .get_route_edges_for(3, 1),
+            road_network.get_route_edges_for(3, 1)
+        );
+
+        // Node 3 at rate 3 is too slow on its own route, but not on 3 -> 14,
+        // once nodes 1 and 2 are safe
+        road_network.evac_info.nodes[0].population = Population::ZERO;
+        road_network.evac_info.nodes[1].population = Population::ZERO;
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(3, 3, 0);
+        road_network.evac_info.nodes[2].max_rate = Rate(20);
+        solution.nodes[0].evacuation_rate = Rate(11);
+        assert!(!solution.check(&road_network));
+        solution.nodes[0].route = 1;
+        assert!(solution.check(&road_network));
+        assert_eq!(solution.makespan(&road_network), Time(21));
+
+        solution.nodes[0].route = 2;
+        assert!(!solution.check(&road_network));
+    }
+}
@@ -0,0 +1,282 @@
+use crate::checker::{DueDateMode, EvacuationSolution, Objective};
+use crate::evac::{parse, read_section, EvacuationInfo};
+use crate::roads::RoadNetwork;
+use crate::solver::greedy;
+use crate::units::{Population, Time};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Populations of some evacuation nodes in one possible future, the other
+/// nodes keeping the population of the instance.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Scenario {
+    /// Relative likelihood of the scenario.
+    pub weight: f32,
+    pub populations: HashMap<u32, Population>,
+}
+
+/// Scenarios a plan is evaluated on. An empty set stands for the populations
+/// of the instance.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScenarioSet {
+    pub scenarios: Vec<Scenario>,
+}
+
+/// Outcome of a plan over a set of scenarios.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RobustReport {
+    /// Share of the total weight of the scenarios where the plan is valid.
+    pub feasibility_rate: f32,
+    pub worst_makespan: Time,
+    /// Makespan averaged over the scenarios with their weights.
+    pub expected_makespan: f32,
+}
+
+impl Scenario {
+    /// Copy of the network with the populations of the scenario.
+    pub fn apply(&self, roads: &RoadNetwork) -> RoadNetwork {
+        let mut result = roads.clone();
+
+        for node in result.evac_info.nodes.iter_mut() {
+            if let Some(population) = self.populations.get(&node.id) {
+                node.population = *population;
+            }
+        }
+        result
+    }
+}
+
+impl ScenarioSet {
+    /// Read the scenarios listed in the `c [scenarios]` section of a `.full`
+    /// file, the set being empty without it.
+    pub fn from_file(filestr: &str) -> Result<ScenarioSet, &'static str> {
+        let mut result = ScenarioSet::default();
+
+        if let Some(rows) = read_section(filestr, "c [scenarios]", 2)? {
+            for words in rows {
+                let count = parse::<usize>(words[1])?;
+                if words.len() < 2 + 2 * count {
+                    return Err("Error while parsing");
+                }
+
+                let mut populations = HashMap::new();
+                for pair in words[2..2 + 2 * count].chunks(2) {
+                    populations.insert(parse(pair[0])?, parse(pair[1])?);
+                }
+                result.scenarios.push(Scenario {
+                    weight: parse(words[0])?,
+                    populations,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Write the scenarios in the `c [scenarios]` section format.
+    pub fn to_file(&self) -> String {
+        let mut result = String::from("c [scenarios] format: header with <num scenarios> then one line per scenario <weight> <k> <id 1> <population 1> ... <id k> <population k>\n");
+        result.push_str(format!("{}\n", self.scenarios.len()).as_str());
+
+        for scenario in &self.scenarios {
+            let mut populations: Vec<_> = scenario.populations.iter().collect();
+            populations.sort();
+
+            result.push_str(format!("{} {}", scenario.weight, populations.len()).as_str());
+            for (id, population) in populations {
+                result.push_str(format!(" {} {}", id, population).as_str());
+            }
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Draws `count` equally likely scenarios, the population of each node
+    /// being uniform in its range. Nodes without a range keep their
+    /// population. The same seed always gives the same scenarios.
+    pub fn sample(evac_info: &EvacuationInfo, count: usize, seed: u64) -> ScenarioSet {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut ranges: Vec<_> = evac_info.population_ranges.iter().collect();
+        ranges.sort();
+
+        let scenarios = (0..count)
+            .map(|_| Scenario {
+                weight: 1.0,
+                populations: ranges
+                    .iter()
+                    .map(|(id, (min, max))| (**id, Population(rng.gen_range(min.0..=max.0))))
+                    .collect(),
+            })
+            .collect();
+        ScenarioSet { scenarios }
+    }
+
+    /// Networks of the scenarios with their weights.
+    fn networks(&self, roads: &RoadNetwork) -> Vec<(f32, RoadNetwork)> {
+        if self.scenarios.is_empty() {
+            return vec![(1.0, roads.clone())];
+        }
+        self.scenarios
+            .iter()
+            .map(|scenario| (scenario.weight, scenario.apply(roads)))
+            .collect()
+    }
+
+    /// Checks and evaluates a plan in every scenario.
+    pub fn evaluate(&self, roads: &RoadNetwork, solution: &EvacuationSolution) -> RobustReport {
+        let mut feasible = 0.0;
+        let mut total = 0.0;
+        let mut worst_makespan = Time::ZERO;
+        let mut expected_makespan = 0.0;
+
+        for (weight, network) in self.networks(roads) {
+            let makespan = solution.makespan(&network);
+
+            if solution.check(&network) {
+                feasible += weight;
+            }
+            total += weight;
+            worst_makespan = worst_makespan.max(makespan);
+            expected_makespan += weight * makespan.0 as f32;
+        }
+
+        RobustReport {
+            feasibility_rate: feasible / total,
+            worst_makespan,
+            expected_makespan: expected_makespan / total,
+        }
+    }
+
+    /// Copy of the network where every node has its largest population over
+    /// the scenarios, or its own one when there is none.
+    pub fn worst_case(&self, roads: &RoadNetwork) -> RoadNetwork {
+        let mut result = roads.clone();
+
+        for node in result.evac_info.nodes.iter_mut() {
+            if let Some(population) = self
+                .scenarios
+                .iter()
+                .map(|scenario| {
+                    scenario
+                        .populations
+                        .get(&node.id)
+                        .copied()
+                        .unwrap_or(node.population)
+                })
+                .max()
+            {
+                node.population = population;
+            }
+        }
+        result
+    }
+}
+
+/// Builds a plan minimizing the worst makespan over the scenarios.
+///
+/// With their rates and start dates fixed, nodes with fewer people leave
+/// earlier and load the roads less. A plan valid for the largest population
+/// of every node is thus valid in every scenario, and its makespan there is
+/// the worst one: the greedy solver is run on that worst case.
+pub fn robust(
+    roads: &RoadNetwork,
+    scenarios: &ScenarioSet,
+    due_dates: DueDateMode,
+) -> EvacuationSolution {
+    greedy(&scenarios.worst_case(roads), Objective::Makespan, due_dates)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::DueDateMode;
+    use crate::evac::EvacuationInfo;
+    use crate::roads::RoadNetwork;
+    use crate::scenarios::{robust, ScenarioSet};
+    use crate::tests::{example_network, example_solution, EXAMPLE};
+    use crate::units::{Population, Time};
+
+    #[test]
+    fn test_parsing_scenarios() {
+        let file_str = format!(
+            "{}c [scenarios] blabla\n2\n0.75 1 1 60\n0.25 2 1 70 3 40\n",
+            EXAMPLE
+        );
+        let scenarios = ScenarioSet::from_file(&file_str).unwrap();
+        assert_eq!(scenarios.scenarios.len(), 2);
+        assert_eq!(scenarios.scenarios[1].weight, 0.25);
+        assert_eq!(scenarios.scenarios[1].populations[&3], Population(40));
+        assert_eq!(
+            scenarios,
+            ScenarioSet::from_file(&scenarios.to_file()).unwrap()
+        );
+        assert_eq!(
+            ScenarioSet::from_file(EXAMPLE).unwrap(),
+            ScenarioSet::default()
+        );
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut evac_info = EvacuationInfo::from_file(EXAMPLE).unwrap();
+        evac_info
+            .population_ranges
+            .insert(1, (Population(40), Population(60)));
+
+        let scenarios = ScenarioSet::sample(&evac_info, 20, 42);
+        assert_eq!(scenarios, ScenarioSet::sample(&evac_info, 20, 42));
+        assert_eq!(scenarios.scenarios.len(), 20);
+        for scenario in &scenarios.scenarios {
+            assert!((Population(40)..=Population(60)).contains(&scenario.populations[&1]));
+            assert!(!scenario.populations.contains_key(&2));
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let road_network = example_network();
+        let solution = example_solution();
+
+        let report = ScenarioSet::default().evaluate(&road_network, &solution);
+        assert_eq!(report.feasibility_rate, 1.0);
+        assert_eq!(report.worst_makespan, Time(37));
+
+        // Node 1 needs 12 then 14 time units to leave
+        let file_str = format!("{}c [scenarios]\n2\n3 1 1 60\n1 1 1 70\n", EXAMPLE);
+        let report = ScenarioSet::from_file(&file_str)
+            .unwrap()
+            .evaluate(&road_network, &solution);
+        assert_eq!(report.worst_makespan, Time(41));
+        assert_eq!(report.expected_makespan, (3.0 * 39.0 + 41.0) / 4.0);
+    }
+
+    #[test]
+    fn test_robust() {
+        let road_network = example_network();
+        let mut evac_info = road_network.evac_info.clone();
+        evac_info
+            .population_ranges
+            .insert(1, (Population(30), Population(70)));
+        evac_info
+            .population_ranges
+            .insert(3, (Population(20), Population(50)));
+        let scenarios = ScenarioSet::sample(&evac_info, 30, 7);
+
+        let solution = robust(&road_network, &scenarios, DueDateMode::Soft { weight: 0.0 });
+        assert!(solution.valid);
+
+        let worst_case = scenarios.worst_case(&road_network);
+        let population =
+            |roads: &RoadNetwork, id| roads.evac_info.get_evacuation_data(id).unwrap().population;
+        let largest = scenarios.scenarios.iter().map(|s| s.populations[&3]).max();
+        assert_eq!(Some(population(&worst_case, 3)), largest);
+        assert_eq!(population(&worst_case, 2), Population(30));
+
+        let report = scenarios.evaluate(&road_network, &solution);
+        assert_eq!(report.feasibility_rate, 1.0);
+        assert_eq!(report.worst_makespan, solution.makespan(&worst_case));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::checker::EvacuationSolution;
+use crate::roads::RoadNetwork;
+use crate::units::{Rate, Time};
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+
+/// Part of an instance whose capacity can be increased.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Element {
+    /// Road between two nodes, in both orientations, the smaller id first.
+    Edge(u32, u32),
+    /// Evacuation node, whose maximum rate is increased.
+    Node(u32),
+}
+
+/// Makespan reached by a solver once the capacity of an element is
+/// increased.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sensitivity {
+    pub element: Element,
+    /// `None` when the solver found no valid solution.
+    pub makespan: Option<Time>,
+    /// Decrease of the makespan compared to the original instance.
+    pub benefit: Option<i64>,
+}
+
+/// Sensitivities of every element of an instance, by decreasing benefit.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SensitivityReport {
+    pub makespan: Option<Time>,
+    pub step: u32,
+    pub elements: Vec<Sensitivity>,
+}
+
+impl Element {
+    /// Copy of the network where the element can take `step` more evacuees
+    /// per time unit. The capacity changes of an edge are raised as well,
+    /// closures excepted.
+    pub fn widen(&self, roads: &RoadNetwork, step: u32) -> RoadNetwork {
+        let mut result = roads.clone();
+
+        match *self {
+            Element::Edge(a, b) => {
+                for edge in result.edges_mut() {
+                    if (edge.parent.min(edge.child), edge.parent.max(edge.child)) != (a, b) {
+                        continue;
+                    }
+                    edge.capacity += Rate(step);
+                    for change in edge.capacity_changes.iter_mut() {
+                        if change.capacity > Rate::ZERO {
+                            change.capacity += Rate(step);
+                        }
+                    }
+                }
+            }
+            Element::Node(id) => {
+                for node in result.evac_info.nodes.iter_mut() {
+                    if node.id == id {
+                        node.max_rate += Rate(step);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+impl SensitivityReport {
+    /// Writes the report as CSV, one line per element by decreasing benefit.
+    /// Elements without a valid solution have empty values.
+    pub fn to_csv(&self) -> String {
+        let mut result = String::from("kind,element,makespan,benefit\n");
+        let value = |value: Option<String>| value.unwrap_or_default();
+
+        for sensitivity in &self.elements {
+            let element = match sensitivity.element {
+                Element::Edge(a, b) => format!("edge,{}-{}", a, b),
+                Element::Node(id) => format!("node,{}", id),
+            };
+            result.push_str(
+                format!(
+                    "{},{},{}\n",
+                    element,
+                    value(sensitivity.makespan.map(|m| m.to_string())),
+                    value(sensitivity.benefit.map(|b| b.to_string()))
+                )
+                .as_str(),
+            );
+        }
+        result
+    }
+}
+
+/// Measures how much the makespan found by `solver` drops when the capacity
+/// of each road used by an escape route, or the maximum rate of each
+/// evacuation node, is increased by `step`.
+pub fn sensitivity<F>(roads: &RoadNetwork, solver: F, step: u32) -> SensitivityReport
+where
+    F: Fn(&RoadNetwork) -> EvacuationSolution,
+{
+    let makespan = |roads: &RoadNetwork| {
+        let solution = solver(roads);
+        if solution.valid {
+            Some(solution.makespan(roads))
+        } else {
+            None
+        }
+    };
+    let base = makespan(roads);
+
+    let mut elements: BTreeSet<Element> = BTreeSet::new();
+    for node in &roads.evac_info.nodes {
+        elements.insert(Element::Node(node.id));
+
+        for i in 0..roads.evac_info.get_routes(node.id).len() {
+            for edge in roads.get_route_edges_for(node.id, i) {
+                let (a, b) = (edge.parent.min(edge.child), edge.parent.max(edge.child));
+                elements.insert(Element::Edge(a, b));
+            }
+        }
+    }
+
+    let mut result: Vec<Sensitivity> = elements
+        .into_iter()
+        .map(|element| {
+            let makespan = makespan(&element.widen(roads, step));
+            Sensitivity {
+                element,
+                makespan,
+                benefit: base
+                    .zip(makespan)
+                    .map(|(base, makespan)| base.0 as i64 - makespan.0 as i64),
+            }
+        })
+        .collect();
+    // Stable: equal benefits keep the order of the elements.
+    result.sort_by_key(|sensitivity| Reverse(sensitivity.benefit));
+
+    SensitivityReport {
+        makespan: base,
+        step,
+        elements: result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::{DueDateMode, Objective};
+    use crate::roads::RoadNetwork;
+    use crate::sensitivity::{sensitivity, Element};
+    use crate::solver::greedy;
+    use crate::tests::example_network;
+    use crate::units::Rate;
+
+    #[test]
+    fn test_widen() {
+        let road_network = example_network();
+
+        let widened = Element::Edge(11, 12).widen(&road_network, 5);
+        assert_eq!(widened.get_edge(11, 12).unwrap().capacity, Rate(15));
+        assert_eq!(widened.get_edge(12, 13).unwrap().capacity, Rate(11));
+
+        let widened = Element::Node(2).widen(&road_network, 5);
+        assert_eq!(
+            widened.evac_info.get_evacuation_data(2).unwrap().max_rate,
+            Rate(8)
+        );
+    }
+
+    #[test]
+    fn test_sensitivity() {
+        let road_network = example_network();
+        let solver = |roads: &RoadNetwork| greedy(roads, Objective::Makespan, DueDateMode::Hard);
+        let report = sensitivity(&road_network, solver, 5);
+
+        assert_eq!(
+            report.makespan,
+            Some(solver(&road_network).makespan(&road_network))
+        );
+        assert_eq!(report.elements.len(), 8);
+        // The road to the safe node is the bottleneck.
+        assert_eq!(report.elements[0].element, Element::Edge(12, 13));
+        assert!(report.elements[0].benefit > Some(0));
+        for pair in report.elements.windows(2) {
+            assert!(pair[0].benefit >= pair[1].benefit);
+        }
+        let csv = report.to_csv();
+        assert!(csv.starts_with("kind,element,makespan,benefit\n"));
+        assert!(csv.contains("\nedge,12-13,"));
+        assert_eq!(csv.lines().count(), 9);
+    }
+}
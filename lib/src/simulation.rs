@@ -0,0 +1,402 @@
+use crate::checker::{EvacuationSolution, Lateness};
+use crate::roads::{RoadEdge, RoadNetwork};
+use crate::units::{Population, Rate, Time};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
+
+/// Outcome of the simulation of an evacuation plan.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SimulationReport {
+    /// Time at which the last evacuee of each node reaches the safe node.
+    pub completion: HashMap<u32, Time>,
+    /// Number of people waiting at each node at every simulated time unit,
+    /// only for the nodes where a queue appeared. Nobody waits at the dates
+    /// left out.
+    pub queues: BTreeMap<u32, BTreeMap<Time, Population>>,
+    /// Time at which the last evacuee reaches the safe node.
+    pub makespan: Time,
+    /// Lateness of the evacuees at the dates they actually entered the edges
+    /// and reached safety.
+    pub lateness: Lateness,
+}
+
+impl SimulationReport {
+    /// Longest queue observed at a node.
+    pub fn max_queue(&self, node_id: u32) -> Population {
+        self.queues
+            .get(&node_id)
+            .and_then(|q| q.values().copied().max())
+            .unwrap_or_default()
+    }
+}
+
+/// People of stream `stream` (index of the solution node they belong to)
+/// reaching `node` at `time`, the last field telling whether they already were
+/// held there.
+type Arrival = Reverse<(Time, u32, usize, Population, bool)>;
+
+/// Queues are identified by the node and the edge they wait for, `None`
+/// standing for the intake of a safe node. They hold `(stream, count)` groups.
+type Queues = BTreeMap<(u32, Option<u32>), VecDeque<(usize, Population)>>;
+
+/// Simulates a plan time unit by time unit, skipping the time units during
+/// which nobody waits nor arrives anywhere.
+///
+/// Evacuees leave their node at the rate and start date of the solution and
+/// move edge by edge along their escape route. At most the current capacity
+/// of an edge enter it per time unit, they reach its end `length` time units
+/// later.
+/// When an edge is saturated, the people who cannot enter it wait in a FIFO
+/// queue at its parent node instead of making the plan infeasible. Holds of
+/// the solution delay the evacuees before they join the queue of a node. Safe
+/// nodes with a limited intake capacity also queue the evacuees they cannot
+/// take in, the simulation fails when a safe node is full while evacuees
+/// still wait to enter it.
+pub fn simulate(
+    roads: &RoadNetwork,
+    solution: &EvacuationSolution,
+) -> Result<SimulationReport, &'static str> {
+    let mut report = SimulationReport::default();
+    let mut arrivals: BinaryHeap<Arrival> = BinaryHeap::new();
+    let mut remaining: HashMap<u32, Population> = HashMap::new();
+    let mut holds: HashMap<(usize, u32), Time> = HashMap::new();
+    let mut next_edges: HashMap<(usize, u32), RoadEdge> = HashMap::new();
+    let mut queues: Queues = BTreeMap::new();
+    let mut sheltered: HashMap<u32, Population> = HashMap::new();
+
+    // Departures are arrivals at the evacuation node itself.
+    for (stream, sol_node) in solution.nodes.iter().enumerate() {
+        let evac_node = roads
+            .evac_info
+            .get_evacuation_data(sol_node.id())
+            .ok_or("Unknown evacuation node")?;
+
+        let population = sol_node.stream_population(evac_node.population);
+
+        if population == Population::ZERO {
+            report
+                .completion
+                .entry(evac_node.id)
+                .or_insert(sol_node.start_date);
+            continue;
+        }
+        if sol_node.evacuation_rate == Rate::ZERO {
+            return Err("Null evacuation rate");
+        }
+
+        for (date, count) in sol_node.departures(population) {
+            arrivals.push(Reverse((date, evac_node.id, stream, count, false)));
+        }
+        for hold in &sol_node.holds {
+            *holds.entry((stream, hold.node)).or_default() += hold.duration;
+        }
+        for edge in roads.get_route_edges_for(evac_node.id, sol_node.route) {
+            next_edges.insert((stream, edge.parent), edge);
+        }
+        *remaining.entry(evac_node.id).or_default() += population;
+    }
+
+    let mut t = Time::ZERO;
+    while !arrivals.is_empty() || queues.values().any(|q| !q.is_empty()) {
+        if queues.values().all(|q| q.is_empty()) {
+            if let Some(Reverse((time, ..))) = arrivals.peek() {
+                t = t.max(*time);
+            }
+        }
+
+        while let Some(Reverse((time, node, stream, count, held))) = arrivals.peek().copied() {
+            if time > t {
+                break;
+            }
+            arrivals.pop();
+
+            if roads.evac_info.is_safe(node) {
+                if roads.evac_info.intake_capacity(node).is_none()
+                    && roads.evac_info.occupancy_capacity(node).is_none()
+                {
+                    let origin = solution.nodes[stream].id();
+                    reach_safety(roads, &mut report, &mut remaining, origin, count, time);
+                } else {
+                    queues
+                        .entry((node, None))
+                        .or_default()
+                        .push_back((stream, count));
+                }
+            } else if let (false, Some(duration)) = (held, holds.get(&(stream, node))) {
+                arrivals.push(Reverse((time + *duration, node, stream, count, true)));
+            } else {
+                let edge = next_edges
+                    .get(&(stream, node))
+                    .ok_or("Route does not reach the safe node")?;
+                queues
+                    .entry((node, Some(edge.child)))
+                    .or_default()
+                    .push_back((stream, count));
+            }
+        }
+
+        let mut waiting: BTreeMap<u32, Population> = BTreeMap::new();
+
+        for ((node, child), queue) in queues.iter_mut() {
+            if queue.is_empty() {
+                continue;
+            }
+
+            let (mut free, edge) = match child {
+                Some(child) => {
+                    let edge = roads.get_edge(*node, *child).unwrap();
+                    (edge.capacity_at(t).during(Time(1)), Some(edge))
+                }
+                None => {
+                    let occupancy = roads.evac_info.occupancy_capacity(*node);
+                    let left = occupancy.map_or(Population::MAX, |occupancy| {
+                        occupancy - sheltered.get(node).copied().unwrap_or_default()
+                    });
+                    if left == Population::ZERO {
+                        return Err("Shelter full");
+                    }
+                    let intake = roads
+                        .evac_info
+                        .intake_capacity(*node)
+                        .map_or(Population::MAX, |intake| intake.during(Time(1)));
+                    (intake.min(left), None)
+                }
+            };
+            // Closed edges keep their queue until they reopen.
+            if free == Population::ZERO && edge.as_ref().is_none_or(|edge| edge.is_closed_from(t)) {
+                return Err("Null capacity on a route edge or safe node");
+            }
+
+            while free > Population::ZERO {
+                match queue.front_mut() {
+                    Some((stream, count)) => {
+                        let moved = free.min(*count);
+                        match &edge {
+                            Some(edge) => {
+                                let delay = edge.due_date.lateness(t);
+                                if delay > 0 {
+                                    let lateness = &mut report.lateness;
+                                    *lateness
+                                        .edges
+                                        .entry((edge.parent, edge.child))
+                                        .or_insert(0) += delay * moved.0 as u64;
+                                    lateness.max = lateness.max.max(delay);
+                                }
+                                arrivals.push(Reverse((
+                                    t + edge.length,
+                                    edge.child,
+                                    *stream,
+                                    moved,
+                                    false,
+                                )))
+                            }
+                            None => {
+                                *sheltered.entry(*node).or_default() += moved;
+                                let origin = solution.nodes[*stream].id();
+                                reach_safety(roads, &mut report, &mut remaining, origin, moved, t)
+                            }
+                        }
+                        *count -= moved;
+                        free -= moved;
+                        if *count == Population::ZERO {
+                            queue.pop_front();
+                        }
+                    }
+                    None => break,
+                }
+            }
+
+            *waiting.entry(*node).or_default() += queue.iter().map(|(_, count)| count).sum();
+        }
+
+        for (node, waiting) in waiting {
+            if waiting > Population::ZERO || report.queues.contains_key(&node) {
+                report.queues.entry(node).or_default().insert(t, waiting);
+            }
+        }
+
+        t += Time(1);
+    }
+
+    Ok(report)
+}
+
+fn reach_safety(
+    roads: &RoadNetwork,
+    report: &mut SimulationReport,
+    remaining: &mut HashMap<u32, Population>,
+    origin: u32,
+    count: Population,
+    time: Time,
+) {
+    if let Some(deadline) = roads.evac_info.deadline(origin) {
+        let delay = (time - deadline).0 as u64;
+        if delay > 0 {
+            *report.lateness.nodes.entry(origin).or_insert(0) += delay * count.0 as u64;
+            report.lateness.max = report.lateness.max.max(delay);
+        }
+    }
+    let left = remaining.get_mut(&origin).unwrap();
+    *left -= count;
+    if *left == Population::ZERO {
+        report.completion.insert(origin, time);
+        report.makespan = report.makespan.max(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::{EvacuationSolution, Hold};
+    use crate::evac::{EvacuationInfo, NodeCapacity};
+    use crate::roads::{RoadEdge, RoadNetwork};
+    use crate::simulation::simulate;
+    use crate::tests::{example_network, example_solution, EXAMPLE};
+    use crate::units::{DueDate, Population, Rate, Time};
+
+    #[test]
+    fn test_simulate_feasible_plan() {
+        let solution = example_solution();
+
+        let report = simulate(&example_network(), &solution).unwrap();
+        assert_eq!(report.makespan, Time(37));
+        assert_eq!(report.completion[&2], Time(34));
+        assert!(report.queues.is_empty());
+        assert_eq!(report.lateness, solution.lateness(&example_network()));
+    }
+
+    #[test]
+    fn test_simulate_late_start() {
+        // The clock skips the time units during which nothing happens.
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(3, 5, 300_000_000);
+
+        let report = simulate(&example_network(), &solution).unwrap();
+        assert_eq!(report.makespan, Time(300_000_028));
+        assert_eq!(report.queues[&3][&Time(300_000_000)], Population(2));
+    }
+
+    #[test]
+    fn test_simulate_saturated_plan() {
+        // Node 3 sends 5 people per time unit on an edge of capacity 3.
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(3, 5, 0);
+
+        let report = simulate(&example_network(), &solution).unwrap();
+        assert_eq!(report.makespan, Time(28));
+        assert_eq!(report.max_queue(3), Population(12));
+        assert_eq!(report.queues[&3][&Time(0)], Population(2));
+        assert_eq!(report.max_queue(12), Population(0));
+    }
+
+    #[test]
+    fn test_simulate_holds() {
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(1, 7, 0);
+        solution.add_node(2, 3, 0);
+        solution.add_node(3, 3, 0);
+
+        // Node 1 and node 3 compete for 12 -> 13 at t=16
+        let report = simulate(&example_network(), &solution).unwrap();
+        assert_eq!(report.max_queue(12), Population(2));
+
+        solution.nodes[0].holds.push(Hold {
+            node: 11,
+            duration: Time(1),
+        });
+        let report = simulate(&example_network(), &solution).unwrap();
+        assert!(report.queues.is_empty());
+        assert_eq!(report.completion[&1], Time(35));
+    }
+
+    #[test]
+    fn test_simulate_intake_capacity() {
+        let mut road_network = example_network();
+        road_network.evac_info.safe_nodes.insert(13, Some(Rate(6)));
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(3, 3, 0);
+        solution.add_node(2, 3, 0);
+
+        let report = simulate(&road_network, &solution).unwrap();
+        assert_eq!(report.max_queue(13), Population(0));
+
+        road_network.evac_info.safe_nodes.insert(13, Some(Rate(5)));
+        let report = simulate(&road_network, &solution).unwrap();
+        assert!(report.max_queue(13) > Population::ZERO);
+        assert!(report.completion[&3] > Time(28));
+    }
+
+    #[test]
+    fn test_simulate_shelter_full() {
+        let mut road_network = example_network();
+        let solution = example_solution();
+
+        road_network.evac_info.node_capacities.insert(
+            13,
+            NodeCapacity {
+                intake: None,
+                occupancy: Some(Population(111)),
+            },
+        );
+        assert_eq!(
+            simulate(&road_network, &solution).unwrap().makespan,
+            Time(37)
+        );
+
+        road_network.evac_info.node_capacities.insert(
+            13,
+            NodeCapacity {
+                intake: None,
+                occupancy: Some(Population(100)),
+            },
+        );
+        assert_eq!(simulate(&road_network, &solution), Err("Shelter full"));
+    }
+
+    #[test]
+    fn test_simulate_road_closure() {
+        let file_str = format!("{}c [capacity changes]\n1\n12 13 1 10 0\n", EXAMPLE);
+        let closed_network =
+            RoadNetwork::from_file(&file_str, EvacuationInfo::from_file(&file_str).unwrap())
+                .unwrap();
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_node(3, 3, 0);
+
+        assert_eq!(
+            simulate(&closed_network, &solution),
+            Err("Null capacity on a route edge or safe node")
+        );
+
+        // Reopened at t=20, 21 people wait at node 12 meanwhile.
+        let file_str = format!("{}c [capacity changes]\n1\n12 13 2 10 0 20 11\n", EXAMPLE);
+        let road_network =
+            RoadNetwork::from_file(&file_str, EvacuationInfo::from_file(&file_str).unwrap())
+                .unwrap();
+        let report = simulate(&road_network, &solution).unwrap();
+        assert_eq!(report.max_queue(12), Population(21));
+        assert_eq!(report.completion[&3], Time(33));
+    }
+
+    #[test]
+    fn test_simulate_streams() {
+        let mut road_network = example_network();
+        road_network
+            .evac_info
+            .alternative_routes
+            .insert(3, vec![vec![11, 12, 13]]);
+        road_network.insert_edge(RoadEdge {
+            parent: 3,
+            child: 11,
+            due_date: DueDate::At(Time(40)),
+            length: Time(3),
+            capacity: Rate(3),
+            capacity_changes: vec![],
+        });
+        let mut solution = EvacuationSolution::new("example");
+        solution.add_stream(3, 3, 0, 0, 18);
+        solution.add_stream(3, 3, 0, 1, 15);
+
+        let report = simulate(&road_network, &solution).unwrap();
+        assert!(report.queues.is_empty());
+        assert_eq!(report.completion[&3], Time(28));
+    }
+}
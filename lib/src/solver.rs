@@ -0,0 +1,411 @@
+use crate::checker::{DueDateMode, EdgeLoad, EvacuationSolution, FlowProfile, Objective};
+use crate::compact::CompactNetwork;
+use crate::roads::{Network, RoadNetwork};
+use crate::units::{Population, Rate, Time};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Instant;
+
+/// Builds a solution by scheduling the evacuation nodes one after the other,
+/// by priority class then in the order of the instance.
+///
+/// Each node gets the rate giving the partial solution its best goal for
+/// `objective`, among the highest rate its route and its safe node can take
+/// and its halves. It leaves at the earliest date keeping the partial
+/// solution valid for the checker: all the objectives only grow when a node
+/// leaves later. Priority classes thus meet their deadlines first, the
+/// makespan comes next. With soft due dates, a node may leave late rather
+/// than delay the evacuation. The solution is marked invalid when a node
+/// cannot be scheduled, for instance when its shelter is too small.
+pub fn greedy(
+    roads: &RoadNetwork,
+    objective: Objective,
+    due_dates: DueDateMode,
+) -> EvacuationSolution {
+    schedule(
+        roads,
+        EvacuationSolution::new("greedy"),
+        whole_nodes(roads),
+        objective,
+        due_dates,
+    )
+}
+
+/// Like [`greedy`], the evacuation nodes of each priority class being taken
+/// in a random order drawn from `seed`.
+pub fn shuffled_greedy(
+    roads: &RoadNetwork,
+    objective: Objective,
+    due_dates: DueDateMode,
+    seed: u64,
+) -> EvacuationSolution {
+    let mut streams = whole_nodes(roads);
+    streams.shuffle(&mut StdRng::seed_from_u64(seed));
+
+    schedule(
+        roads,
+        EvacuationSolution::new("shuffled_greedy"),
+        streams,
+        objective,
+        due_dates,
+    )
+}
+
+/// One stream per evacuation node, in the order of the instance.
+fn whole_nodes(roads: &RoadNetwork) -> Vec<Stream> {
+    roads
+        .evac_info
+        .nodes
+        .iter()
+        .map(|node| Stream {
+            id: node.id,
+            route: 0,
+            share: None,
+            release: Time::ZERO,
+        })
+        .collect()
+}
+
+/// People of an evacuation node left to schedule by [`schedule`].
+pub(crate) struct Stream {
+    pub id: u32,
+    /// Index of the escape route taken.
+    pub route: usize,
+    /// Number of people of the stream, `None` for the whole population.
+    pub share: Option<Population>,
+    /// Earliest date the stream can leave.
+    pub release: Time,
+}
+
+/// Adds `streams` to a partial solution the way [`greedy`] adds the
+/// evacuation nodes, around the streams already in it.
+pub(crate) fn schedule(
+    roads: &RoadNetwork,
+    mut solution: EvacuationSolution,
+    mut streams: Vec<Stream>,
+    objective: Objective,
+    due_dates: DueDateMode,
+) -> EvacuationSolution {
+    let earlier = Instant::now();
+    let network = CompactNetwork::new(roads);
+    solution.valid = true;
+
+    // Scheduling the streams one after the other, once every capacity change
+    // and the streams already there are over, always fits in this horizon.
+    let horizon: Time = streams
+        .iter()
+        .filter_map(|stream| {
+            let node = network.evacuation_node(stream.id)?;
+            let edges = network.route_edges(stream.id, stream.route);
+            let length: Time = edges.iter().map(|e| e.length).sum();
+            let changes = edges
+                .iter()
+                .flat_map(|e| e.capacity_changes.iter().map(|change| change.date))
+                .max()
+                .unwrap_or(Time::ZERO);
+            let population = stream.share.unwrap_or(node.population);
+            Some(length + population.duration_at(Rate(1)) + changes + Time(1))
+        })
+        .sum::<Time>()
+        + streams
+            .iter()
+            .map(|stream| stream.release)
+            .max()
+            .unwrap_or(Time::ZERO)
+        + solution.makespan(&network);
+
+    streams.sort_by_key(|stream| {
+        let class = network.evac_info().priority_class(stream.id);
+        (class.is_none(), class)
+    });
+
+    for stream in streams {
+        let node = match network.evacuation_node(stream.id) {
+            Some(node) => node,
+            None => {
+                solution.valid = false;
+                continue;
+            }
+        };
+        let add = |solution: &mut EvacuationSolution, rate: Rate, start: Time| {
+            solution.add_node(stream.id, rate.0, start.0);
+            let sol_node = solution.nodes.last_mut().unwrap();
+            sol_node.route = stream.route;
+            sol_node.share = stream.share;
+        };
+
+        let route = network.route_edges(node.id, stream.route);
+        let mut rate = route
+            .iter()
+            .map(|edge| edge.capacity)
+            .fold(node.max_rate, Rate::min);
+        if let Some(intake) = route
+            .last()
+            .and_then(|edge| network.evac_info().intake_capacity(edge.child))
+        {
+            rate = rate.min(intake);
+        }
+        let rate = rate.max(Rate(1));
+
+        if !route
+            .last()
+            .is_some_and(|edge| network.evac_info().is_safe(edge.child))
+        {
+            solution.valid = false;
+            add(&mut solution, rate, stream.release);
+            continue;
+        }
+
+        let profile = solution.flow_profile(&network);
+        // The node starts once the previous priority classes are evacuated.
+        let release = match network.evac_info().priority_class(node.id) {
+            Some(class) => solution
+                .completions(&network)
+                .iter()
+                .filter(|(id, _)| {
+                    network
+                        .evac_info()
+                        .priority_class(**id)
+                        .is_some_and(|other| other < class)
+                })
+                .map(|(_, completion)| *completion + Time(1))
+                .max()
+                .unwrap_or(Time::ZERO),
+            None => Time::ZERO,
+        }
+        .max(stream.release);
+        let mut scheduled: Option<(f32, EvacuationSolution)> = None;
+        for rate in rates(rate) {
+            // Leaving later only shifts the loads of the node.
+            let mut alone = EvacuationSolution::new("greedy");
+            add(&mut alone, rate, Time::ZERO);
+            let extra = alone.flow_profile(&network);
+            let latest = match due_dates {
+                DueDateMode::Hard => latest_start(&network, &alone, &extra),
+                DueDateMode::Soft { .. } => Some(horizon),
+            };
+            let start = latest.and_then(|latest| {
+                (release.0..=latest.min(horizon).0)
+                    .map(Time)
+                    .find(|start| fits(&network, &profile, &extra, *start))
+            });
+
+            if let Some(start) = start {
+                let mut candidate = solution.clone();
+                add(&mut candidate, rate, start);
+                let goal = candidate.goal(&network, objective, due_dates);
+                if scheduled.as_ref().is_none_or(|(best, _)| goal < *best) {
+                    scheduled = Some((goal, candidate));
+                }
+            }
+        }
+
+        match scheduled {
+            Some((_, candidate)) => solution = candidate,
+            None => {
+                solution.valid = false;
+                add(&mut solution, node.max_rate, stream.release);
+            }
+        }
+    }
+
+    solution.goal_value = solution.goal(&network, objective, due_dates);
+    solution.compute_time = earlier.elapsed().as_secs_f32();
+    solution
+}
+
+/// Rates tried for a node: its highest one and its halves, down to a
+/// quarter of it.
+fn rates(max: Rate) -> Vec<Rate> {
+    let mut result = vec![max, Rate(max.0 / 2), Rate(max.0 / 4)];
+    result.dedup();
+    result.retain(|rate| *rate > Rate::ZERO);
+    result
+}
+
+/// Latest date a node can leave without anybody entering an edge after its
+/// due date or reaching safety after its deadline, from its solution and
+/// loads when it leaves at date 0.
+fn latest_start(
+    network: &CompactNetwork,
+    alone: &EvacuationSolution,
+    extra: &FlowProfile,
+) -> Option<Time> {
+    let mut result = Time::MAX;
+
+    for ((parent, child), load) in &extra.edges {
+        let due_date = network.edge(*parent, *child).unwrap().due_date;
+        if let Some((_, end)) = load.span() {
+            let latest = due_date.last_entry()?.checked_sub(end - Time(1))?;
+            result = result.min(latest);
+        }
+    }
+    for sol_node in &alone.nodes {
+        if let Some(deadline) = network.evac_info().deadline(sol_node.id()) {
+            result = result.min(deadline.checked_sub(alone.makespan(network))?);
+        }
+    }
+    Some(result)
+}
+
+/// Tells whether the loads of `extra`, delayed by `shift`, fit in the
+/// capacities left by `base`.
+fn fits(network: &CompactNetwork, base: &FlowProfile, extra: &FlowProfile, shift: Time) -> bool {
+    fits_loads(&base.edges, &extra.edges, shift)
+        && fits_loads(&base.departures, &extra.departures, shift)
+        && fits_loads(&base.buffers, &extra.buffers, shift)
+        && fits_loads(&base.intakes, &extra.intakes, shift)
+        && extra.occupancies.iter().all(|(id, load)| {
+            if network.evac_info().is_safe(*id) {
+                // Occupancies of safe nodes only grow, their last value holds
+                // on.
+                let sheltered = |profile: &FlowProfile| {
+                    profile
+                        .occupancies
+                        .get(id)
+                        .and_then(|load| load.counts().last())
+                        .map_or(Population::ZERO, |(_, count)| count)
+                };
+                sheltered(base) + sheltered(extra) <= load.capacity
+            } else {
+                fits_load(base.occupancies.get(id), load, shift)
+            }
+        })
+}
+
+fn fits_loads<K: Eq + Hash>(
+    base: &HashMap<K, EdgeLoad>,
+    extra: &HashMap<K, EdgeLoad>,
+    shift: Time,
+) -> bool {
+    extra
+        .iter()
+        .all(|(key, load)| fits_load(base.get(key), load, shift))
+}
+
+fn fits_load(base: Option<&EdgeLoad>, extra: &EdgeLoad, shift: Time) -> bool {
+    extra.counts().all(|(t, count)| {
+        let t = t + shift;
+        count + base.map_or(Population::ZERO, |base| base.count_at(t)) <= extra.capacity_at(t)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::{DueDateMode, Objective};
+    use crate::evac::{NodeCapacity, NodePriority};
+    use crate::solver::{greedy, shuffled_greedy};
+    use crate::tests::example_network;
+    use crate::units::{Population, Rate, Time};
+
+    #[test]
+    fn test_greedy() {
+        let road_network = example_network();
+        let solution = greedy(&road_network, Objective::Makespan, DueDateMode::Hard);
+
+        assert!(solution.valid);
+        assert!(solution.check(&road_network));
+        assert_eq!(
+            solution.goal_value,
+            solution.makespan(&road_network).0 as f32
+        );
+
+        let solution = greedy(&road_network, Objective::TotalPersonTime, DueDateMode::Hard);
+        assert!(solution.check(&road_network));
+        assert_eq!(
+            solution.goal_value,
+            solution.total_person_time(&road_network) as f32
+        );
+
+        let solution = shuffled_greedy(&road_network, Objective::Makespan, DueDateMode::Hard, 3);
+        assert!(solution.check(&road_network));
+        assert!(solution.to_file().starts_with("shuffled_greedy\n"));
+        assert_eq!(
+            solution.nodes,
+            shuffled_greedy(&road_network, Objective::Makespan, DueDateMode::Hard, 3).nodes
+        );
+    }
+
+    #[test]
+    fn test_greedy_node_capacities() {
+        let mut road_network = example_network();
+        let soft = DueDateMode::Soft { weight: 1.0 };
+        road_network.evac_info.node_capacities.insert(
+            13,
+            NodeCapacity {
+                intake: Some(Rate(4)),
+                occupancy: None,
+            },
+        );
+        let solution = greedy(&road_network, Objective::Makespan, soft);
+        assert!(solution.valid);
+        assert!(solution.check(&road_network));
+
+        road_network.evac_info.node_capacities.insert(
+            13,
+            NodeCapacity {
+                intake: None,
+                occupancy: Some(Population(100)),
+            },
+        );
+        let solution = greedy(&road_network, Objective::Makespan, soft);
+        assert!(!solution.valid);
+        assert!(!solution.check(&road_network));
+    }
+
+    #[test]
+    fn test_greedy_soft_due_dates() {
+        // 111 people cannot reach 13 at 4 per time unit before 12 -> 13 is due
+        let mut road_network = example_network();
+        road_network.evac_info.node_capacities.insert(
+            13,
+            NodeCapacity {
+                intake: Some(Rate(4)),
+                occupancy: None,
+            },
+        );
+        assert!(!greedy(&road_network, Objective::Makespan, DueDateMode::Hard).valid);
+
+        let soft = DueDateMode::Soft { weight: 2.0 };
+        let solution = greedy(&road_network, Objective::Makespan, soft);
+        let lateness = solution.lateness(&road_network).total();
+        assert!(solution.valid);
+        assert!(lateness > 0);
+        assert!(!solution.check_due_dates(&road_network, DueDateMode::Hard));
+        assert_eq!(
+            solution.goal_value,
+            solution.makespan(&road_network).0 as f32 + 2.0 * lateness as f32
+        );
+    }
+
+    #[test]
+    fn test_greedy_priorities() {
+        let mut road_network = example_network();
+        road_network.evac_info.priorities.insert(
+            3,
+            NodePriority {
+                class: Some(0),
+                deadline: Some(Time(28)),
+            },
+        );
+        road_network.evac_info.priorities.insert(
+            1,
+            NodePriority {
+                class: Some(1),
+                deadline: None,
+            },
+        );
+
+        let soft = DueDateMode::Soft { weight: 1.0 };
+        let solution = greedy(&road_network, Objective::Makespan, soft);
+        assert!(solution.valid);
+        assert!(solution.check(&road_network));
+        assert_eq!(solution.nodes[0].id(), 3);
+        assert_eq!(solution.completions(&road_network)[&3], Time(28));
+        assert_eq!(solution.nodes[1].id(), 1);
+        assert!(solution.nodes[1].start_date > Time(28));
+    }
+}
@@ -0,0 +1,241 @@
+//! Quantities of the time model.
+//!
+//! Dates, rates and populations get their own types so that they cannot be
+//! mixed up, and their arithmetic saturates instead of overflowing: a date
+//! past `Time::MAX` is as unreachable as `Time::MAX` itself.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+use std::str::FromStr;
+
+/// Date or duration, in time units.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Time(pub u32);
+
+/// Number of evacuees per time unit.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Rate(pub u32);
+
+/// Number of evacuees.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Population(pub u32);
+
+/// Date from which evacuees entering an edge are late.
+///
+/// Files mark edges without due date with a huge value, usually
+/// `9223372036854775807`: any due date beyond `Time::MAX` is read as
+/// `Never`, which is written back as that value.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "u64", into = "u64")
+)]
+pub enum DueDate {
+    At(Time),
+    Never,
+}
+
+/// Value standing for [`DueDate::Never`] in files.
+pub const NEVER: u64 = i64::MAX as u64;
+
+macro_rules! quantity {
+    ($name:ident) => {
+        impl $name {
+            pub const ZERO: $name = $name(0);
+            pub const MAX: $name = $name(u32::MAX);
+
+            pub fn checked_add(self, other: $name) -> Option<$name> {
+                self.0.checked_add(other.0).map($name)
+            }
+
+            pub fn checked_sub(self, other: $name) -> Option<$name> {
+                self.0.checked_sub(other.0).map($name)
+            }
+        }
+
+        /// Saturates at `MAX`.
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name(self.0.saturating_add(other.0))
+            }
+        }
+
+        /// Saturates at zero.
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name(self.0.saturating_sub(other.0))
+            }
+        }
+
+        /// Saturates at `MAX`.
+        impl Mul<u32> for $name {
+            type Output = $name;
+
+            fn mul(self, factor: u32) -> $name {
+                $name(self.0.saturating_mul(factor))
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                *self = *self - other;
+            }
+        }
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = $name>>(iter: I) -> $name {
+                iter.fold($name::ZERO, Add::add)
+            }
+        }
+
+        impl<'a> Sum<&'a $name> for $name {
+            fn sum<I: Iterator<Item = &'a $name>>(iter: I) -> $name {
+                iter.copied().sum()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = std::num::ParseIntError;
+
+            fn from_str(s: &str) -> Result<$name, Self::Err> {
+                s.parse().map($name)
+            }
+        }
+    };
+}
+
+quantity!(Time);
+quantity!(Rate);
+quantity!(Population);
+
+impl Population {
+    /// Number of time units needed to let the population go at `rate`, a
+    /// null rate never letting anybody go.
+    pub fn duration_at(self, rate: Rate) -> Time {
+        match rate.0 {
+            0 if self.0 > 0 => Time::MAX,
+            0 => Time::ZERO,
+            rate => Time(self.0.div_ceil(rate)),
+        }
+    }
+}
+
+impl Rate {
+    /// Evacuees going at this rate during `duration`.
+    pub fn during(self, duration: Time) -> Population {
+        Population(self.0.saturating_mul(duration.0))
+    }
+}
+
+impl DueDate {
+    /// Number of time units an evacuee entering the edge at `date` is late,
+    /// an evacuee entering at the due date being one time unit late.
+    pub fn lateness(self, date: Time) -> u64 {
+        match self {
+            DueDate::At(due) => (date.0 as u64 + 1).saturating_sub(due.0 as u64),
+            DueDate::Never => 0,
+        }
+    }
+
+    /// Last date at which evacuees can enter the edge in time, `None` when
+    /// they are always late.
+    pub fn last_entry(self) -> Option<Time> {
+        match self {
+            DueDate::At(due) => due.checked_sub(Time(1)),
+            DueDate::Never => Some(Time::MAX),
+        }
+    }
+}
+
+impl From<u64> for DueDate {
+    fn from(value: u64) -> DueDate {
+        if value <= u32::MAX as u64 {
+            DueDate::At(Time(value as u32))
+        } else {
+            DueDate::Never
+        }
+    }
+}
+
+impl From<DueDate> for u64 {
+    fn from(due_date: DueDate) -> u64 {
+        match due_date {
+            DueDate::At(due) => due.0 as u64,
+            DueDate::Never => NEVER,
+        }
+    }
+}
+
+impl fmt::Display for DueDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        u64::from(*self).fmt(f)
+    }
+}
+
+impl FromStr for DueDate {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<DueDate, Self::Err> {
+        s.parse::<u64>().map(DueDate::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::units::{DueDate, Population, Rate, Time, NEVER};
+
+    #[test]
+    fn test_saturating_arithmetic() {
+        assert_eq!(Time(3) + Time(4), Time(7));
+        assert_eq!(Time::MAX + Time(1), Time::MAX);
+        assert_eq!(Time(3) - Time(4), Time::ZERO);
+        assert_eq!(Time(3).checked_sub(Time(4)), None);
+        assert_eq!(
+            [Time(u32::MAX - 1), Time(5)].iter().sum::<Time>(),
+            Time::MAX
+        );
+
+        assert_eq!(Population(48).duration_at(Rate(5)), Time(10));
+        assert_eq!(Population(48).duration_at(Rate(0)), Time::MAX);
+        assert_eq!(Rate(5).during(Time(10)), Population(50));
+    }
+
+    #[test]
+    fn test_due_dates() {
+        let never: DueDate = "9223372036854775807".parse().unwrap();
+        assert_eq!(never, DueDate::Never);
+        assert_eq!(never.to_string(), NEVER.to_string());
+        assert_eq!(never.lateness(Time::MAX), 0);
+        assert!(DueDate::At(Time::MAX) < never);
+
+        let due: DueDate = "13".parse().unwrap();
+        assert_eq!(due, DueDate::At(Time(13)));
+        assert_eq!(due.lateness(Time(12)), 0);
+        assert_eq!(due.lateness(Time(13)), 1);
+        assert_eq!(due.last_entry(), Some(Time(12)));
+        assert_eq!(DueDate::At(Time(0)).last_entry(), None);
+    }
+}
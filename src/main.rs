@@ -1,29 +1,406 @@
+use std::env;
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
 
-use lib_mwanamke::checker::EvacuationSolution;
+use lib_mwanamke::checker::{DueDateMode, EvacuationSolution, Objective};
+use lib_mwanamke::contraflow::plan_contraflow;
+use lib_mwanamke::critical::{critical_path, RateLimit};
 use lib_mwanamke::evac::EvacuationInfo;
+use lib_mwanamke::montecarlo::Uncertainty;
+use lib_mwanamke::pareto::pareto_front;
+use lib_mwanamke::plot::plan_to_svg;
+use lib_mwanamke::portfolio::{portfolio, Worker};
 use lib_mwanamke::roads::RoadNetwork;
+use lib_mwanamke::routes::{RoadGraph, RouteCost};
+use lib_mwanamke::scenarios::ScenarioSet;
+use lib_mwanamke::sensitivity::sensitivity;
+use lib_mwanamke::simulation::simulate;
+use lib_mwanamke::solver::{greedy, shuffled_greedy};
 
-fn main() {
-    let mut file = File::open("data/example.full").unwrap();
+const USAGE: &str = "usage:
+    mwanamke check <instance.full> <solution.sol>
+    mwanamke convert <input> <output>  (serde feature)
+    mwanamke dot <instance.full> [solution.sol]
+    mwanamke plot <instance.full> <solution.sol> -o <plan.svg>
+    mwanamke simulate <instance.full> <solution.sol>
+    mwanamke routes <instance.full> <k> [length|capacity] -o <output.full>
+    mwanamke pareto <instance.full> -o <front.csv>
+    mwanamke robust <instance.full> <solution.sol>
+    mwanamke montecarlo <instance.full> <solution.sol> [runs] [seed]
+    mwanamke sensitivity <instance.full> [step] -o <table.csv>
+    mwanamke explain <instance.full> <solution.sol>
+    mwanamke contraflow <instance.full> <budget> -o <output.full>
+    mwanamke portfolio <instance.full> <seconds> -o <solution.sol>";
+
+fn read_file(path: &str) -> String {
+    let mut file = File::open(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
 
     let mut file_str = String::new();
     file.read_to_string(&mut file_str).unwrap();
+    file_str
+}
+
+fn write_file(path: &str, content: &str) {
+    let mut file = File::create(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    file.write_all(content.as_bytes()).unwrap();
+}
 
-    let evac_info = EvacuationInfo::from_file(&file_str).unwrap();
-    let road_network = RoadNetwork::from_file(&file_str, evac_info).unwrap();
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
 
-    file = File::open("data/example.sol").unwrap();
+fn read_instance(path: &str) -> RoadNetwork {
+    let file_str = read_file(path);
 
-    file_str = String::new();
-    file.read_to_string(&mut file_str).unwrap();
+    let evac_info = EvacuationInfo::from_file(&file_str).unwrap_or_else(|e| fail(e));
+    RoadNetwork::from_file(&file_str, evac_info).unwrap_or_else(|e| fail(e))
+}
+
+fn read_solution(path: &str) -> EvacuationSolution {
+    EvacuationSolution::from_file(&read_file(path)).unwrap_or_else(|e| fail(e))
+}
 
-    let solution = EvacuationSolution::from_file(&file_str).unwrap();
+fn check(instance: &str, solution: &str) {
+    let road_network = read_instance(instance);
+    let solution = read_solution(solution);
 
     if solution.check(&road_network) {
         println!("Solution is correct!");
     } else {
         println!("Solution has problems in it");
     }
+
+    let lateness = solution.lateness(&road_network);
+    let mut edges: Vec<_> = lateness.edges.iter().collect();
+    edges.sort();
+    for ((parent, child), late) in edges {
+        println!(
+            "edge {} -> {}: {} person-time units late",
+            parent, child, late
+        );
+    }
+    let mut nodes: Vec<_> = lateness.nodes.iter().collect();
+    nodes.sort();
+    for (id, late) in nodes {
+        println!("node {}: {} person-time units late", id, late);
+    }
+    println!("lateness: {}", lateness.total());
+}
+
+fn dot(instance: &str, solution: Option<&String>) {
+    let road_network = read_instance(instance);
+    let solution = solution.map(|path| read_solution(path));
+
+    print!("{}", road_network.to_dot(solution.as_ref()));
+}
+
+fn plot(instance: &str, solution: &str, output: &str) {
+    let road_network = read_instance(instance);
+    let solution = read_solution(solution);
+
+    write_file(output, &plan_to_svg(&road_network, &solution));
+}
+
+fn simulation(instance: &str, solution: &str) {
+    let road_network = read_instance(instance);
+    let solution = read_solution(solution);
+    let report = simulate(&road_network, &solution).unwrap_or_else(|e| fail(e));
+
+    let mut completion: Vec<_> = report.completion.iter().collect();
+    completion.sort();
+    for (id, date) in completion {
+        println!(
+            "node {}: completed at {}, max queue {}",
+            id,
+            date,
+            report.max_queue(*id)
+        );
+    }
+    for id in report.queues.keys() {
+        if !report.completion.contains_key(id) {
+            println!("node {}: max queue {}", id, report.max_queue(*id));
+        }
+    }
+    println!("makespan: {}", report.makespan);
+}
+
+fn explain(instance: &str, solution: &str) {
+    let road_network = read_instance(instance);
+    let solution = read_solution(solution);
+    let path = match critical_path(&road_network, &solution) {
+        Some(path) => path,
+        None => fail("Nobody is evacuated"),
+    };
+
+    println!("makespan: {}", path.makespan);
+    println!(
+        "node {}: leaves from {} to {} at rate {}, then travels {} time units",
+        path.node, path.departure.0, path.departure.1, path.rate, path.route_delay
+    );
+    match path.limit {
+        RateLimit::MaxRate => println!("rate limited by the max rate of the node"),
+        RateLimit::Edge(parent, child) => {
+            println!("rate limited by the capacity of {} -> {}", parent, child)
+        }
+        RateLimit::Intake(id) => println!("rate limited by the intake of node {}", id),
+        RateLimit::Plan => println!("rate below every capacity of the route"),
+    }
+    for edge in &path.edges {
+        let competitors: Vec<String> = edge.competitors.iter().map(u32::to_string).collect();
+        println!(
+            "edge {} -> {}: entered from {} to {}, saturated {} time units, shared with [{}]",
+            edge.parent,
+            edge.child,
+            edge.window.0,
+            edge.window.1,
+            edge.saturated,
+            competitors.join(", ")
+        );
+    }
+}
+
+fn routes(instance: &str, k: &str, cost: &str, output: &str) {
+    let mut road_network = read_instance(instance);
+    let graph = RoadGraph::from_file(&read_file(instance)).unwrap_or_else(|e| fail(e));
+    let k = k.parse::<usize>().unwrap_or_else(|_| fail(USAGE));
+    let cost = match cost {
+        "length" => RouteCost::Length,
+        "capacity" => RouteCost::CapacityAware,
+        _ => fail(USAGE),
+    };
+
+    graph.add_alternative_routes(&mut road_network, k, cost);
+    write_file(output, &road_network.to_file());
+}
+
+fn contraflow(instance: &str, budget: &str, output: &str) {
+    let road_network = read_instance(instance);
+    let budget = budget.parse::<u32>().unwrap_or_else(|_| fail(USAGE));
+    let plan = plan_contraflow(&road_network, budget, DueDateMode::Soft { weight: 0.0 });
+
+    for contraflow in &plan.reversed {
+        println!(
+            "reverse {} - {}: cost {}, from {}",
+            contraflow.node1, contraflow.node2, contraflow.cost, contraflow.delay
+        );
+    }
+    println!("cost: {}", plan.cost);
+    println!("makespan: {}", plan.solution.makespan(&plan.network));
+    write_file(output, &plan.network.to_file());
+}
+
+fn pareto(instance: &str, output: &str) {
+    let road_network = read_instance(instance);
+    let objectives = [
+        Objective::Makespan,
+        Objective::TotalPersonTime,
+        Objective::TotalLateness,
+    ];
+
+    write_file(output, &pareto_front(&road_network, &objectives).to_csv());
+}
+
+/// Scenarios drawn when the instance lists none.
+const SAMPLED_SCENARIOS: usize = 100;
+
+fn robust(instance: &str, solution: &str) {
+    let file_str = read_file(instance);
+    let road_network = read_instance(instance);
+    let solution = read_solution(solution);
+
+    let mut scenarios = ScenarioSet::from_file(&file_str).unwrap_or_else(|e| fail(e));
+    if scenarios.scenarios.is_empty() {
+        scenarios = ScenarioSet::sample(&road_network.evac_info, SAMPLED_SCENARIOS, 0);
+    }
+    let report = scenarios.evaluate(&road_network, &solution);
+
+    println!("feasibility rate: {}", report.feasibility_rate);
+    println!("worst makespan: {}", report.worst_makespan);
+    println!("expected makespan: {}", report.expected_makespan);
+}
+
+fn monte_carlo(instance: &str, solution: &str, runs: Option<&String>, seed: Option<&String>) {
+    let file_str = read_file(instance);
+    let road_network = read_instance(instance);
+    let solution = read_solution(solution);
+    let uncertainty = Uncertainty::from_file(&file_str).unwrap_or_else(|e| fail(e));
+
+    let runs = runs.map_or(Ok(1000), |runs| runs.parse::<usize>());
+    let seed = seed.map_or(Ok(0), |seed| seed.parse::<u64>());
+    let (runs, seed) = match (runs, seed) {
+        (Ok(runs), Ok(seed)) => (runs, seed),
+        _ => fail(USAGE),
+    };
+    let report = uncertainty.monte_carlo(&road_network, &solution, runs, seed);
+
+    println!("mean makespan: {}", report.mean_makespan());
+    for q in &[0.0, 0.5, 0.9, 0.99, 1.0] {
+        if let Some(makespan) = report.quantile(*q) {
+            println!("makespan quantile {}: {}", q, makespan);
+        }
+    }
+    println!(
+        "capacity violation probability: {}",
+        report.capacity_violation_probability()
+    );
+    println!(
+        "due date violation probability: {}",
+        report.due_date_violation_probability()
+    );
+}
+
+fn sensitivity_table(instance: &str, step: &str, output: &str) {
+    let road_network = read_instance(instance);
+    let step = step.parse::<u32>().unwrap_or_else(|_| fail(USAGE));
+    // Due dates are left aside, as by the checker.
+    let solver = |roads: &RoadNetwork| {
+        greedy(
+            roads,
+            Objective::Makespan,
+            DueDateMode::Soft { weight: 0.0 },
+        )
+    };
+
+    write_file(output, &sensitivity(&road_network, solver, step).to_csv());
+}
+
+fn portfolio_solve(instance: &str, seconds: &str, output: &str) {
+    let road_network = read_instance(instance);
+    let seconds = seconds.parse::<u64>().unwrap_or_else(|_| fail(USAGE));
+    // Due dates are left aside, as by the checker.
+    let (objective, due_dates) = (Objective::Makespan, DueDateMode::Soft { weight: 0.0 });
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut workers = vec![Worker::new("greedy", 0, Some(1), |roads, _, _| {
+        greedy(roads, objective, due_dates)
+    })];
+    for i in 1..threads.max(2) {
+        workers.push(Worker::new(
+            &format!("shuffled greedy {}", i),
+            (i as u64) << 32,
+            None,
+            |roads, seed, _| shuffled_greedy(roads, objective, due_dates, seed),
+        ));
+    }
+    let report = portfolio(
+        &road_network,
+        &workers,
+        objective,
+        due_dates,
+        Duration::from_secs(seconds),
+    );
+
+    for worker in &report.workers {
+        println!(
+            "{}: {} runs, {} valid, best {}, {} improvements",
+            worker.name,
+            worker.runs,
+            worker.valid_runs,
+            worker
+                .best_goal
+                .map_or(String::from("-"), |goal| goal.to_string()),
+            worker.improvements
+        );
+    }
+    match report.best {
+        Some(best) => {
+            println!("makespan: {}", best.goal_value);
+            write_file(output, &best.to_file());
+        }
+        None => fail("no valid solution found"),
+    }
+}
+
+#[cfg(feature = "serde")]
+mod convert {
+    use super::*;
+    use serde_crate::de::DeserializeOwned;
+    use serde_crate::Serialize;
+    use std::path::Path;
+
+    fn extension(path: &str) -> &str {
+        Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+    }
+
+    fn serialize<T: Serialize>(value: &T, format: &str) -> String {
+        match format {
+            "json" => serde_json::to_string_pretty(value).unwrap(),
+            _ => serde_yaml::to_string(value).unwrap(),
+        }
+    }
+
+    fn deserialize<T: DeserializeOwned>(content: &str, format: &str) -> T {
+        match format {
+            "json" => serde_json::from_str(content).unwrap_or_else(|e| fail(&e.to_string())),
+            _ => serde_yaml::from_str(content).unwrap_or_else(|e| fail(&e.to_string())),
+        }
+    }
+
+    /// Convert between the text formats (`.full`, `.sol`) and JSON/YAML.
+    ///
+    /// The kind of data (instance or solution) is given by the extension of
+    /// the text side of the conversion.
+    pub fn convert(input: &str, output: &str) {
+        let content = match (extension(input), extension(output)) {
+            ("full", format @ ("json" | "yaml" | "yml")) => {
+                serialize(&read_instance(input), format)
+            }
+            ("sol", format @ ("json" | "yaml" | "yml")) => serialize(&read_solution(input), format),
+            (format @ ("json" | "yaml" | "yml"), "full") => {
+                deserialize::<RoadNetwork>(&read_file(input), format).to_file()
+            }
+            (format @ ("json" | "yaml" | "yml"), "sol") => {
+                deserialize::<EvacuationSolution>(&read_file(input), format).to_file()
+            }
+            _ => fail("convert: expected one of .full/.sol and one of .json/.yaml"),
+        };
+
+        write_file(output, &content);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        None => check("data/example.full", "data/example.sol"),
+        Some("check") if args.len() == 4 => check(&args[2], &args[3]),
+        #[cfg(feature = "serde")]
+        Some("convert") if args.len() == 4 => convert::convert(&args[2], &args[3]),
+        Some("dot") if args.len() == 3 || args.len() == 4 => dot(&args[2], args.get(3)),
+        Some("plot") if args.len() == 6 && args[4] == "-o" => plot(&args[2], &args[3], &args[5]),
+        Some("simulate") if args.len() == 4 => simulation(&args[2], &args[3]),
+        Some("routes") if args.len() == 6 && args[4] == "-o" => {
+            routes(&args[2], &args[3], "length", &args[5])
+        }
+        Some("routes") if args.len() == 7 && args[5] == "-o" => {
+            routes(&args[2], &args[3], &args[4], &args[6])
+        }
+        Some("pareto") if args.len() == 5 && args[3] == "-o" => pareto(&args[2], &args[4]),
+        Some("robust") if args.len() == 4 => robust(&args[2], &args[3]),
+        Some("sensitivity") if args.len() == 5 && args[3] == "-o" => {
+            sensitivity_table(&args[2], "1", &args[4])
+        }
+        Some("sensitivity") if args.len() == 6 && args[4] == "-o" => {
+            sensitivity_table(&args[2], &args[3], &args[5])
+        }
+        Some("explain") if args.len() == 4 => explain(&args[2], &args[3]),
+        Some("contraflow") if args.len() == 6 && args[4] == "-o" => {
+            contraflow(&args[2], &args[3], &args[5])
+        }
+        Some("portfolio") if args.len() == 6 && args[4] == "-o" => {
+            portfolio_solve(&args[2], &args[3], &args[5])
+        }
+        Some("montecarlo") if (4..=6).contains(&args.len()) => {
+            monte_carlo(&args[2], &args[3], args.get(4), args.get(5))
+        }
+        _ => fail(USAGE),
+    }
 }